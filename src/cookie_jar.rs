@@ -0,0 +1,203 @@
+//! Reading and writing Netscape cookie jar files, the format used by curl and wget.
+
+use chromiumoxide::cdp::browser_protocol::network::{Cookie, CookieParam, TimeSinceEpoch};
+use std::fmt;
+
+/// curl marks http-only cookies by prefixing the domain with this.
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JarCookie {
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    /// Unix timestamp, `0` for session cookies.
+    pub expires: i64,
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(contents: &str) -> Result<Vec<JarCookie>, ParseError> {
+    let mut cookies = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let error = |message: &str| ParseError {
+            line: index + 1,
+            message: message.to_string(),
+        };
+
+        let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return Err(error("expected 7 tab separated fields"));
+        }
+
+        cookies.push(JarCookie {
+            domain: fields[0].to_string(),
+            include_subdomains: parse_bool(fields[1])
+                .ok_or_else(|| error("include subdomains flag must be TRUE or FALSE"))?,
+            path: fields[2].to_string(),
+            secure: parse_bool(fields[3])
+                .ok_or_else(|| error("secure flag must be TRUE or FALSE"))?,
+            http_only,
+            expires: fields[4]
+                .parse()
+                .map_err(|_| error("expiry must be a unix timestamp"))?,
+            name: fields[5].to_string(),
+            value: fields[6].to_string(),
+        });
+    }
+
+    Ok(cookies)
+}
+
+fn parse_bool(field: &str) -> Option<bool> {
+    match field {
+        "TRUE" => Some(true),
+        "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+fn format_bool(value: bool) -> &'static str {
+    if value { "TRUE" } else { "FALSE" }
+}
+
+pub fn serialize(cookies: &[JarCookie]) -> String {
+    let mut out = String::from("# Netscape HTTP Cookie File\n");
+    for cookie in cookies {
+        if cookie.http_only {
+            out.push_str(HTTP_ONLY_PREFIX);
+        }
+        out.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            cookie.domain,
+            format_bool(cookie.include_subdomains),
+            cookie.path,
+            format_bool(cookie.secure),
+            cookie.expires,
+            cookie.name,
+            cookie.value,
+        ));
+    }
+    out
+}
+
+/// Replaces the cookies in `jar` that share domain, path and name with one in `updates`, and
+/// appends the rest.
+pub fn merge(jar: &mut Vec<JarCookie>, updates: impl IntoIterator<Item = JarCookie>) {
+    for update in updates {
+        let existing = jar.iter_mut().find(|cookie| {
+            same_domain(&cookie.domain, &update.domain)
+                && cookie.path == update.path
+                && cookie.name == update.name
+        });
+        match existing {
+            Some(cookie) => *cookie = update,
+            None => jar.push(update),
+        }
+    }
+}
+
+/// Chrome reports domain cookies with a leading dot that jar files often leave out.
+fn same_domain(a: &str, b: &str) -> bool {
+    a.trim_start_matches('.') == b.trim_start_matches('.')
+}
+
+impl JarCookie {
+    pub fn to_param(&self) -> Result<CookieParam, String> {
+        let mut param = CookieParam::builder()
+            .name(&self.name)
+            .value(&self.value)
+            .path(&self.path)
+            .secure(self.secure)
+            .http_only(self.http_only);
+        // Setting a domain always makes a domain cookie, host-only ones are tied to a url instead.
+        param = if self.include_subdomains {
+            param.domain(&self.domain)
+        } else {
+            let scheme = if self.secure { "https" } else { "http" };
+            let host = self.domain.trim_start_matches('.');
+            param.url(format!("{scheme}://{host}{}", self.path))
+        };
+        if self.expires > 0 {
+            param = param.expires(TimeSinceEpoch::new(self.expires as f64));
+        }
+        param.build()
+    }
+}
+
+impl From<&Cookie> for JarCookie {
+    fn from(cookie: &Cookie) -> Self {
+        JarCookie {
+            domain: cookie.domain.clone(),
+            include_subdomains: cookie.domain.starts_with('.'),
+            path: cookie.path.clone(),
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+            expires: if cookie.session {
+                0
+            } else {
+                cookie.expires as i64
+            },
+            name: cookie.name.clone(),
+            value: cookie.value.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JAR: &str = "# Netscape HTTP Cookie File\n\
+        .example.com\tTRUE\t/\tFALSE\t0\tsession\tabc\n\
+        #HttpOnly_example.com\tFALSE\t/app\tTRUE\t1900000000\ttoken\txyz\n";
+
+    #[test]
+    fn round_trips() {
+        let cookies = parse(JAR).unwrap();
+        assert_eq!(cookies.len(), 2);
+        assert!(!cookies[0].http_only);
+        assert!(cookies[1].http_only);
+        assert_eq!(cookies[1].domain, "example.com");
+        assert_eq!(cookies[1].expires, 1900000000);
+        assert_eq!(serialize(&cookies), JAR);
+    }
+
+    #[test]
+    fn merge_ignores_leading_dot() {
+        let mut jar = parse(JAR).unwrap();
+        let update = JarCookie {
+            domain: ".example.com".to_string(),
+            value: "new".to_string(),
+            ..jar[1].clone()
+        };
+        merge(&mut jar, [update]);
+        assert_eq!(jar.len(), 2);
+        assert_eq!(jar[1].value, "new");
+    }
+}
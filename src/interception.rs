@@ -0,0 +1,193 @@
+//! `--request-interception` and `--no-images`: pausing every request to block it, or to let a
+//! Nu closure decide its fate.
+
+use chromiumoxide::{
+    Page,
+    cdp::browser_protocol::{
+        fetch::{
+            ContinueRequestParams, EnableParams, EventRequestPaused, FailRequestParams,
+            HeaderEntry, RequestPattern,
+        },
+        network::{ErrorReason, ResourceType},
+    },
+    error::CdpError,
+};
+use futures::StreamExt;
+use nu_plugin::EngineInterface;
+use nu_protocol::{ShellError, Span, Spanned, Value, engine::Closure};
+use std::sync::{Arc, Mutex};
+
+use crate::{AbortOnDrop, json_to_value};
+
+/// The closure given to `--request-interception`, along with the engine to run it on.
+pub struct RequestInterception {
+    pub engine: EngineInterface,
+    pub closure: Spanned<Closure>,
+}
+
+enum Action {
+    Abort,
+    Continue(ContinueRequestParams),
+}
+
+impl RequestInterception {
+    fn request_value(event: &EventRequestPaused, span: Span) -> Value {
+        let request = &event.request;
+        Value::record(
+            [
+                ("url", Value::string(request.url.clone(), span)),
+                ("method", Value::string(request.method.clone(), span)),
+                (
+                    "headers",
+                    json_to_value(request.headers.inner().clone(), span),
+                ),
+                (
+                    "resource_type",
+                    Value::string(event.resource_type.as_ref(), span),
+                ),
+            ]
+            .into_iter()
+            .map(|(column, value)| (column.to_string(), value))
+            .collect(),
+            span,
+        )
+    }
+
+    /// An empty result lets the request through, `{abort: true}` blocks it, and `url`, `method`
+    /// or `headers` columns override those parts of the request.
+    fn parse_action(event: &EventRequestPaused, output: Value) -> Result<Action, Box<ShellError>> {
+        let mut params = ContinueRequestParams::new(event.request_id.clone());
+        let record = match output {
+            Value::Nothing { .. } => return Ok(Action::Continue(params)),
+            Value::Record { val, .. } => val,
+            other => {
+                return Err(Box::new(ShellError::TypeMismatch {
+                    err_message: format!(
+                        "the request interception closure must return a record, got {}",
+                        other.get_type()
+                    ),
+                    span: other.span(),
+                }));
+            }
+        };
+
+        if let Some(abort) = record.get("abort")
+            && abort.as_bool()?
+        {
+            return Ok(Action::Abort);
+        }
+        if let Some(url) = record.get("url") {
+            params.url = Some(url.coerce_string()?);
+        }
+        if let Some(method) = record.get("method") {
+            params.method = Some(method.coerce_string()?);
+        }
+        if let Some(headers) = record.get("headers") {
+            params.headers = Some(
+                headers
+                    .as_record()?
+                    .iter()
+                    .map(|(name, value)| Ok(HeaderEntry::new(name.clone(), value.coerce_string()?)))
+                    .collect::<Result<_, Box<ShellError>>>()?,
+            );
+        }
+        Ok(Action::Continue(params))
+    }
+
+    fn decide(&self, event: &EventRequestPaused) -> Result<Action, Box<ShellError>> {
+        let span = self.closure.span;
+        let request = Self::request_value(event, span);
+        let output =
+            self.engine
+                .eval_closure(&self.closure, vec![request.clone()], Some(request))?;
+        Self::parse_action(event, output)
+    }
+}
+
+/// Answers every paused request until dropped.
+pub struct Interceptor {
+    error: Arc<Mutex<Option<Box<ShellError>>>>,
+    _task: AbortOnDrop,
+}
+
+impl Interceptor {
+    /// A closure needs a browser launched with `enable_request_intercept`, which pauses every
+    /// request. Without one, only requests of the blocked types are paused.
+    pub async fn start(
+        page: &Page,
+        interception: Option<Arc<RequestInterception>>,
+        blocked_types: Vec<ResourceType>,
+    ) -> Result<Self, CdpError> {
+        let mut paused = page.event_listener::<EventRequestPaused>().await?;
+        if interception.is_none() {
+            let patterns = blocked_types
+                .iter()
+                .map(|resource_type| RequestPattern {
+                    resource_type: Some(resource_type.clone()),
+                    ..RequestPattern::default()
+                })
+                .collect();
+            page.execute(EnableParams {
+                patterns: Some(patterns),
+                ..EnableParams::default()
+            })
+            .await?;
+        }
+        let error = Arc::new(Mutex::new(None));
+        let task = tokio::spawn({
+            let page = page.clone();
+            let error = error.clone();
+            async move {
+                while let Some(event) = paused.next().await {
+                    let unchanged =
+                        || Action::Continue(ContinueRequestParams::new(event.request_id.clone()));
+                    let action = if blocked_types.contains(&event.resource_type) {
+                        Action::Abort
+                    } else if let Some(interception) = &interception {
+                        // Evaluating the closure blocks until the engine answers.
+                        let decision = tokio::task::spawn_blocking({
+                            let interception = interception.clone();
+                            let event = event.clone();
+                            move || interception.decide(&event)
+                        })
+                        .await;
+                        match decision {
+                            Ok(Ok(action)) => action,
+                            // Keep the page loading so the error can be reported once it is done.
+                            Ok(Err(e)) => {
+                                error.lock().unwrap().get_or_insert(e);
+                                unchanged()
+                            }
+                            Err(_) => unchanged(),
+                        }
+                    } else {
+                        unchanged()
+                    };
+                    let _ = match action {
+                        Action::Abort => page
+                            .execute(FailRequestParams::new(
+                                event.request_id.clone(),
+                                ErrorReason::BlockedByClient,
+                            ))
+                            .await
+                            .map(drop),
+                        Action::Continue(params) => page.execute(params).await.map(drop),
+                    };
+                }
+            }
+        });
+
+        Ok(Interceptor {
+            error,
+            _task: AbortOnDrop(task),
+        })
+    }
+
+    /// The first error the closure raised, if any.
+    pub fn finish(self) -> Result<(), Box<ShellError>> {
+        match self.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
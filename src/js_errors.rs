@@ -0,0 +1,92 @@
+//! Uncaught JavaScript exceptions, collected from the DevTools `Runtime.exceptionThrown` event.
+
+use chromiumoxide::{Page, cdp::js_protocol::runtime::EventExceptionThrown, error::CdpError};
+use futures::StreamExt;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+use crate::AbortOnDrop;
+
+pub struct JsError {
+    pub message: String,
+    url: Option<String>,
+    line_number: i64,
+    column_number: i64,
+    stack_trace: Vec<serde_json::Value>,
+}
+
+impl JsError {
+    fn from_event(event: &EventExceptionThrown) -> Self {
+        let details = &event.exception_details;
+        // `text` is only "Uncaught", the thrown value carries the actual message.
+        let message = details
+            .exception
+            .as_ref()
+            .and_then(|exception| exception.description.clone())
+            .unwrap_or_else(|| details.text.clone());
+        // The protocol counts lines and columns from zero.
+        let stack_trace = details
+            .stack_trace
+            .iter()
+            .flat_map(|trace| &trace.call_frames)
+            .map(|frame| {
+                json!({
+                    "function_name": frame.function_name,
+                    "url": frame.url,
+                    "line_number": frame.line_number + 1,
+                    "column_number": frame.column_number + 1,
+                })
+            })
+            .collect();
+
+        JsError {
+            message,
+            url: details.url.clone(),
+            line_number: details.line_number + 1,
+            column_number: details.column_number + 1,
+            stack_trace,
+        }
+    }
+
+    pub fn into_json(self) -> serde_json::Value {
+        json!({
+            "message": self.message,
+            "url": self.url,
+            "line_number": self.line_number,
+            "column_number": self.column_number,
+            "stack_trace": self.stack_trace,
+        })
+    }
+}
+
+/// Records every exception the page doesn't catch.
+pub struct JsErrorCapture {
+    errors: Arc<Mutex<Vec<JsError>>>,
+    _task: AbortOnDrop,
+}
+
+impl JsErrorCapture {
+    /// Must be started before navigation so errors thrown during load are seen.
+    pub async fn start(page: &Page) -> Result<Self, CdpError> {
+        let mut thrown = page.event_listener::<EventExceptionThrown>().await?;
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let task = tokio::spawn({
+            let errors = errors.clone();
+            async move {
+                while let Some(event) = thrown.next().await {
+                    errors.lock().unwrap().push(JsError::from_event(&event));
+                }
+            }
+        });
+
+        Ok(JsErrorCapture {
+            errors,
+            _task: AbortOnDrop(task),
+        })
+    }
+
+    /// Errors caught so far, in the order they were thrown.
+    pub fn finish(self) -> Vec<JsError> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+}
@@ -1,13 +1,59 @@
-use chromiumoxide::{Browser, BrowserConfig};
+use chromiumoxide::{
+    Browser, BrowserConfig, Page,
+    cdp::browser_protocol::{
+        dom::{GetDocumentParams, GetOuterHtmlParams},
+        emulation::{MediaFeature, SetEmulatedMediaParams, SetTouchEmulationEnabledParams},
+        network::{
+            Cookie, Headers, ResourceType, SetBypassServiceWorkerParams, SetExtraHttpHeadersParams,
+        },
+        page::{
+            EventDomContentEventFired, EventJavascriptDialogOpening, HandleJavaScriptDialogParams,
+            PrintToPdfParams,
+        },
+        performance, service_worker,
+    },
+    error::CdpError,
+    handler::viewport::Viewport,
+    js::Evaluation,
+    page::MediaTypeParams,
+};
+use cookie_jar::JarCookie;
 use futures::StreamExt;
+use interception::{Interceptor, RequestInterception};
+use js_errors::JsErrorCapture;
 use nu_plugin::{
     EngineInterface, EvaluatedCall, MsgPackSerializer, Plugin, SimplePluginCommand, serve_plugin,
 };
-use nu_protocol::{Category, Example, LabeledError, Signature, SyntaxShape, Value};
-use std::{error::Error, time::Duration};
+use nu_protocol::{
+    Category, Example, LabeledError, Record, Signature, Span, Spanned, SyntaxShape, Value,
+};
+use pdf::HttpBrowsePdf;
+use regex::Regex;
+use resource_timing::ResourceTimingCapture;
+use response_hook::ResponseHook;
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs, io,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::{runtime::Runtime, task::JoinHandle};
+use websocket::{Frame, MessagePattern, WebSocketCapture};
+
+mod cookie_jar;
+mod interception;
+mod js_errors;
+mod pdf;
+mod resource_timing;
+mod response_hook;
+mod selector;
+mod websocket;
 
 #[derive(Clone)]
-struct HttpBrowse;
+pub struct HttpBrowse;
 
 impl Plugin for HttpBrowse {
     fn version(&self) -> String {
@@ -15,7 +61,7 @@ impl Plugin for HttpBrowse {
     }
 
     fn commands(&self) -> Vec<Box<dyn nu_plugin::PluginCommand<Plugin = Self>>> {
-        vec![Box::new(HttpBrowse)]
+        vec![Box::new(HttpBrowse), Box::new(HttpBrowsePdf)]
     }
 }
 
@@ -29,13 +75,323 @@ impl SimplePluginCommand for HttpBrowse {
         Signature::build("http browse")
             .required("url", SyntaxShape::String, "The URL to browse")
             .switch("no-stealth", "Disable stealth mode", None)
+            .switch(
+                "title-only",
+                "Only return the page title, as soon as DOMContentLoaded fires",
+                None,
+            )
             .switch("with-head", "Disable headless mode", None)
+            .switch(
+                "disable-gpu",
+                "Run without GPU or sandbox, as needed in most containers and CI",
+                None,
+            )
+            .switch(
+                "no-sandbox",
+                "Run chrome without its sandbox, needed in most Docker and CI containers",
+                None,
+            )
+            .switch(
+                "single-process",
+                "Run chrome in a single process to fit tiny containers; less stable",
+                None,
+            )
+            .named(
+                "headless-version",
+                SyntaxShape::String,
+                "Headless mode to run chrome in: new (default) or old",
+                None,
+            )
+            .switch(
+                "disable-notifications",
+                "Deny notification, geolocation and camera permission prompts",
+                None,
+            )
+            .switch(
+                "emulate-print-media",
+                "Apply `@media print` CSS rules before the page loads, as `http browse pdf` always does when printing",
+                None,
+            )
+            .switch(
+                "prefers-reduced-data",
+                "Emulate a metered connection with `prefers-reduced-data` and `Save-Data: on`",
+                None,
+            )
+            .switch(
+                "service-worker-bypass",
+                "Skip service workers and clear Cache API storage so the response is network-fresh",
+                None,
+            )
+            .named(
+                "extra-js-contexts",
+                SyntaxShape::OneOf(vec![
+                    SyntaxShape::String,
+                    SyntaxShape::List(Box::new(SyntaxShape::String)),
+                ]),
+                "Script, or list of scripts run in order, defining globals before any page script runs",
+                None,
+            )
+            .named(
+                "content-css",
+                SyntaxShape::OneOf(vec![
+                    SyntaxShape::Filepath,
+                    SyntaxShape::List(Box::new(SyntaxShape::Filepath)),
+                ]),
+                "Stylesheet, or list of stylesheets, to add to the page before the HTML is captured",
+                None,
+            )
+            .named(
+                "browser-language",
+                SyntaxShape::String,
+                "Language tag, e.g. fr-FR, used for both `navigator.language` and `Accept-Language`",
+                None,
+            )
+            .named(
+                "window-size",
+                SyntaxShape::String,
+                "Viewport size as <width>x<height>, e.g. 1920x1080",
+                None,
+            )
+            .switch(
+                "simulate-touch",
+                "Enable touch events and a mobile viewport",
+                None,
+            )
+            .named(
+                "emulate-battery",
+                SyntaxShape::Record(vec![]),
+                "Battery state reported by `navigator.getBattery()`, e.g. {charging: false, level: 0.15}",
+                None,
+            )
             .named(
                 "wait",
                 SyntaxShape::Duration,
                 "Time to wait before retrieving html",
                 Some('w'),
             )
+            .named(
+                "wait-min",
+                SyntaxShape::Duration,
+                "Minimum time between navigation start and retrieving html, however fast the page goes idle",
+                None,
+            )
+            .named(
+                "wait-for-frames",
+                SyntaxShape::Int,
+                "After the network is idle, wait until this many animation frames have been painted",
+                None,
+            )
+            .named(
+                "wait-for-idle-cpu",
+                SyntaxShape::Number,
+                "Experimental: after the network is idle, wait up to 30s until CPU usage drops below this percentage",
+                None,
+            )
+            .named(
+                "wait-for-selector-to-disappear",
+                SyntaxShape::String,
+                "After the network is idle, wait up to 30s until no visible element matches this CSS selector",
+                None,
+            )
+            .named(
+                "wait-for-attribute",
+                SyntaxShape::String,
+                "After the network is idle, wait up to 30s until the element matching this CSS selector has --wait-for-attribute-name set to --wait-for-attribute-value",
+                None,
+            )
+            .named(
+                "wait-for-attribute-name",
+                SyntaxShape::String,
+                "Attribute to watch for --wait-for-attribute, e.g. data-app-state",
+                None,
+            )
+            .named(
+                "wait-for-attribute-value",
+                SyntaxShape::String,
+                "Value --wait-for-attribute waits for, e.g. ready",
+                None,
+            )
+            .switch(
+                "dump-dom",
+                "Serialize the HTML from the DOM tree through the DevTools protocol instead of the renderer",
+                None,
+            )
+            .named(
+                "clip-html",
+                SyntaxShape::String,
+                "Return the whole document, but with only the element matching this CSS selector left in the body",
+                None,
+            )
+            .named(
+                "selector",
+                SyntaxShape::String,
+                "Return the outer HTML of the first element matching this CSS selector",
+                None,
+            )
+            .switch(
+                "shadow-dom",
+                "Let selectors match elements inside shadow roots",
+                None,
+            )
+            .switch(
+                "extract-links",
+                "Return the absolute URLs of all links instead of the HTML",
+                None,
+            )
+            .named(
+                "link-filter",
+                SyntaxShape::String,
+                "Only keep extracted links matching this regex",
+                None,
+            )
+            .switch("no-dedup", "Keep duplicate extracted links", None)
+            .switch(
+                "extract-feeds",
+                "Return the RSS, Atom and JSON Feed links of the page instead of the HTML",
+                None,
+            )
+            .switch(
+                "extract-canonical",
+                "Return the canonical URL of the page instead of the HTML (always part of --structured)",
+                None,
+            )
+            .switch(
+                "extract-hreflang",
+                "Return the language alternates (`hreflang` links) of the page instead of the HTML",
+                None,
+            )
+            .switch(
+                "extract-resource-hints",
+                "Return the preload, prefetch, preconnect and modulepreload links of the page instead of the HTML",
+                None,
+            )
+            .switch(
+                "extract-microdata",
+                "Return the HTML Microdata (itemscope/itemprop) items of the page instead of the HTML",
+                None,
+            )
+            .named(
+                "network-idle-threshold",
+                SyntaxShape::Int,
+                "Milliseconds without network activity before the page counts as loaded (default 500)",
+                None,
+            )
+            .named(
+                "js-dialog-response",
+                SyntaxShape::String,
+                "How to answer alert/confirm/prompt dialogs: accept, dismiss (default) or text:<string>",
+                None,
+            )
+            .named(
+                "port",
+                SyntaxShape::Int,
+                "Bind the DevTools protocol to this port instead of a random one",
+                Some('p'),
+            )
+            .switch(
+                "capture-websocket",
+                "Record WebSocket frames under `websocket_frames` (implies --structured)",
+                None,
+            )
+            .named(
+                "capture-performance-entries",
+                SyntaxShape::String,
+                "Comma separated PerformanceObserver entry types to collect under `performance_entries` (implies --structured)",
+                None,
+            )
+            .switch(
+                "capture-resource-timing",
+                "Record when each resource loaded and how big it was under `resource_timing` (implies --structured)",
+                None,
+            )
+            .switch(
+                "capture-errors",
+                "Record uncaught JavaScript exceptions under `errors` (implies --structured)",
+                None,
+            )
+            .switch(
+                "fail-on-js-error",
+                "Fail if the page throws an uncaught JavaScript exception",
+                None,
+            )
+            .named(
+                "evaluate-after-each-request",
+                SyntaxShape::String,
+                "JS expression to evaluate whenever a response arrives, collected under `hook_results` (implies --structured)",
+                None,
+            )
+            .switch(
+                "capture-timing-breakdown",
+                "Record DNS, TCP, TLS, TTFB, download and DOM timings of the document under `timing_breakdown` (implies --structured)",
+                None,
+            )
+            .switch(
+                "capture-storage",
+                "Dump localStorage, sessionStorage and IndexedDB under `storage` (implies --structured)",
+                None,
+            )
+            .named(
+                "idb-database",
+                SyntaxShape::String,
+                "IndexedDB database to dump with --capture-storage (default: the first one)",
+                None,
+            )
+            .named(
+                "wait-for-websocket-message",
+                SyntaxShape::String,
+                "Wait up to 30s for an incoming WebSocket frame containing this text or matching this regex",
+                None,
+            )
+            .switch(
+                "strip-base64",
+                "Replace long base64 data URIs in the HTML with `data:[STRIPPED]`",
+                None,
+            )
+            .named(
+                "strip-base64-threshold",
+                SyntaxShape::Int,
+                "Length in bytes above which --strip-base64 replaces a data URI (default 256)",
+                None,
+            )
+            .named(
+                "response-filter",
+                SyntaxShape::String,
+                "Comma separated HTTP statuses; return nothing when the document response has another one",
+                None,
+            )
+            .switch("no-images", "Block every image request", None)
+            .named(
+                "request-interception",
+                SyntaxShape::Closure(Some(vec![SyntaxShape::Record(vec![])])),
+                "Closure deciding what happens to each request: return {abort: true}, or url/method/headers overrides",
+                None,
+            )
+            .named(
+                "cookies-file",
+                SyntaxShape::Filepath,
+                "Netscape cookie jar to load cookies from before navigating",
+                None,
+            )
+            .switch(
+                "save-cookies",
+                "Write the cookies back to --cookies-file after the page loads",
+                None,
+            )
+            .switch(
+                "structured",
+                "Return a record with the HTML and details about the page load",
+                Some('s'),
+            )
+            .switch(
+                "dry-run",
+                "Validate the arguments and look for chrome, but return them instead of launching it",
+                None,
+            )
+            .switch(
+                "verbose",
+                "Print details about the page load to stderr",
+                Some('v'),
+            )
             .category(Category::Network)
     }
 
@@ -48,67 +404,1382 @@ impl SimplePluginCommand for HttpBrowse {
     }
 
     fn examples(&'_ self) -> Vec<Example<'_>> {
-        vec![Example {
-            description: "Fetch a page and output HTML",
-            example: "http browse https://example.com",
-            result: None,
-        }]
+        vec![
+            Example {
+                description: "Fetch a page and output HTML",
+                example: "http browse https://example.com",
+                result: None,
+            },
+            Example {
+                description: "List the blog post links of a page",
+                example: r#"http browse https://blog.example.com --extract-links --link-filter 'https://blog.example.com/\d{4}/'"#,
+                result: None,
+            },
+        ]
     }
 
     fn run(
         &self,
         _plugin: &HttpBrowse,
-        _engine: &EngineInterface,
+        engine: &EngineInterface,
         call: &EvaluatedCall,
         _input: &Value,
     ) -> Result<Value, LabeledError> {
-        let url: String = call.req(0)?;
+        // `--dry-run` reports the URL that is navigated, so both see it trimmed.
+        let url = call.req::<String>(0)?.trim().to_string();
+        check_flag_conflicts(call)?;
         let disable_stealth = call.has_flag("no-stealth")?;
         let disable_headless = call.has_flag("with-head")?;
+        let title_only = call.has_flag("title-only")?;
+        let old_headless = match call.get_flag::<Spanned<String>>("headless-version")? {
+            Some(version) => match version.item.as_str() {
+                "new" => false,
+                "old" => true,
+                _ => {
+                    return Err(LabeledError::new("Invalid headless version")
+                        .with_label("expected new or old", version.span));
+                }
+            },
+            None => false,
+        };
         let wait = call.get_flag::<Duration>("wait")?;
+        let wait_min = call.get_flag::<Duration>("wait-min")?;
+        let disable_gpu = call.has_flag("disable-gpu")?;
+        let no_sandbox = call.has_flag("no-sandbox")?;
+        let single_process = call.has_flag("single-process")?;
+        let deny_permissions = call.has_flag("disable-notifications")?;
+        let print_media = call.has_flag("emulate-print-media")?;
+        let reduced_data = call.has_flag("prefers-reduced-data")?;
+        let bypass_service_worker = call.has_flag("service-worker-bypass")?;
+        let extra_js_contexts = match call.get_flag::<Value>("extra-js-contexts")? {
+            Some(Value::List { vals, .. }) => vals
+                .iter()
+                .map(Value::coerce_string)
+                .collect::<Result<_, _>>()?,
+            Some(script) => vec![script.coerce_string()?],
+            None => Vec::new(),
+        };
+        let content_css = match call.get_flag::<Value>("content-css")? {
+            Some(Value::List { vals, .. }) => vals,
+            Some(path) => vec![path],
+            None => Vec::new(),
+        }
+        .into_iter()
+        .map(|path| {
+            let span = path.span();
+            let path = Path::new(&engine.get_current_dir()?).join(path.coerce_into_string()?);
+            fs::read_to_string(&path).map_err(|e| {
+                LabeledError::new(format!("Could not read {}: {e}", path.display()))
+                    .with_label("unreadable stylesheet", span)
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+        let browser_language = call
+            .get_flag::<Spanned<String>>("browser-language")?
+            .map(|language| {
+                let valid = language.item.split('-').all(|part| {
+                    !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric())
+                });
+                if valid {
+                    Ok(language.item)
+                } else {
+                    Err(LabeledError::new("Invalid browser language")
+                        .with_label("expected a language tag like fr or fr-FR", language.span))
+                }
+            })
+            .transpose()?;
+        let window_size = call
+            .get_flag::<Spanned<String>>("window-size")?
+            .map(|size| {
+                parse_window_size(&size.item).ok_or_else(|| {
+                    LabeledError::new("Invalid window size").with_label(
+                        "expected <width>x<height> with positive integers",
+                        size.span,
+                    )
+                })
+            })
+            .transpose()?;
+        let simulate_touch = call.has_flag("simulate-touch")?;
+        let battery = call
+            .get_flag::<Value>("emulate-battery")?
+            .map(|value| {
+                let span = value.span();
+                Battery::from_value(&value).ok_or_else(|| {
+                    LabeledError::new("Invalid battery state").with_label(
+                        "expected {charging: <bool>, level: <number between 0 and 1>}",
+                        span,
+                    )
+                })
+            })
+            .transpose()?;
+        let disappear_selector = call.get_flag::<String>("wait-for-selector-to-disappear")?;
+        let attribute_wait = match (
+            call.get_flag::<Spanned<String>>("wait-for-attribute")?,
+            call.get_flag::<Spanned<String>>("wait-for-attribute-name")?,
+            call.get_flag::<Spanned<String>>("wait-for-attribute-value")?,
+        ) {
+            (Some(selector), Some(name), Some(value)) => Some(AttributeWait {
+                selector: selector.item,
+                name: name.item,
+                value: value.item,
+            }),
+            (None, None, None) => None,
+            (selector, name, value) => {
+                let span = [selector, name, value]
+                    .into_iter()
+                    .flatten()
+                    .map(|flag| flag.span)
+                    .next()
+                    .unwrap_or(call.head);
+                return Err(LabeledError::new("Incomplete attribute wait").with_label(
+                    "--wait-for-attribute, --wait-for-attribute-name and --wait-for-attribute-value go together",
+                    span,
+                ));
+            }
+        };
+        let selector = call.get_flag::<String>("selector")?;
+        let clip_html = call.get_flag::<String>("clip-html")?;
+        let dump_dom = call.has_flag("dump-dom")?;
+        let shadow_dom = call.has_flag("shadow-dom")?;
+        let extract_feeds = call.has_flag("extract-feeds")?;
+        let extract_canonical = call.has_flag("extract-canonical")?;
+        let extract_hreflang = call.has_flag("extract-hreflang")?;
+        let extract_resource_hints = call.has_flag("extract-resource-hints")?;
+        let extract_microdata = call.has_flag("extract-microdata")?;
+        let capture_websocket = call.has_flag("capture-websocket")?;
+        let websocket_message = call
+            .get_flag::<String>("wait-for-websocket-message")?
+            .map(MessagePattern::new);
+        let response_filter = call
+            .get_flag::<Spanned<String>>("response-filter")?
+            .map(|statuses| {
+                parse_statuses(&statuses.item).ok_or_else(|| {
+                    LabeledError::new("Invalid response filter").with_label(
+                        "expected comma separated HTTP status codes, e.g. 200,301",
+                        statuses.span,
+                    )
+                })
+            })
+            .transpose()?;
+        let strip_base64 = if call.has_flag("strip-base64")? {
+            match call.get_flag::<Spanned<i64>>("strip-base64-threshold")? {
+                Some(threshold) => Some(usize::try_from(threshold.item).map_err(|_| {
+                    LabeledError::new("Invalid base64 threshold")
+                        .with_label("expected a non-negative number of bytes", threshold.span)
+                })?),
+                None => Some(256),
+            }
+        } else {
+            None
+        };
+        let no_images = call.has_flag("no-images")?;
+        let request_interception = call.get_flag("request-interception")?.map(|closure| {
+            Arc::new(RequestInterception {
+                engine: engine.clone(),
+                closure,
+            })
+        });
+        let cookies_file = call.get_flag::<Spanned<PathBuf>>("cookies-file")?;
+        let save_cookies = call.has_flag("save-cookies")?;
+        let loaded_jar = match &cookies_file {
+            Some(file) => {
+                let path = Path::new(&engine.get_current_dir()?).join(&file.item);
+                let jar = load_cookie_jar(&path, save_cookies).map_err(|e| {
+                    LabeledError::new(format!("Could not load cookies: {e}"))
+                        .with_label("invalid cookie jar", file.span)
+                })?;
+                Some((path, jar))
+            }
+            None if save_cookies => {
+                return Err(LabeledError::new("--save-cookies needs a file to write to")
+                    .with_label(
+                        "requires --cookies-file",
+                        call.get_flag_span("save-cookies").unwrap_or(call.head),
+                    ));
+            }
+            None => None,
+        };
+        let performance_entry_types: Vec<String> = call
+            .get_flag::<String>("capture-performance-entries")?
+            .map(|types| {
+                types
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let capture_resource_timing = call.has_flag("capture-resource-timing")?;
+        let capture_errors = call.has_flag("capture-errors")?;
+        let capture_timing_breakdown = call.has_flag("capture-timing-breakdown")?;
+        let fail_on_js_error = call.has_flag("fail-on-js-error")?;
+        let response_hook = call.get_flag::<String>("evaluate-after-each-request")?;
+        let storage_capture = if call.has_flag("capture-storage")? {
+            Some(StorageCapture {
+                idb_database: call.get_flag("idb-database")?,
+            })
+        } else {
+            None
+        };
+        let structured = call.has_flag("structured")?
+            || capture_websocket
+            || capture_resource_timing
+            || capture_errors
+            || capture_timing_breakdown
+            || response_hook.is_some()
+            || storage_capture.is_some()
+            || !performance_entry_types.is_empty();
+        let verbose = call.has_flag("verbose")?;
+        let idle_threshold = match call.get_flag::<Spanned<i64>>("network-idle-threshold")? {
+            Some(ms) => u64::try_from(ms.item)
+                .map(Duration::from_millis)
+                .map_err(|_| {
+                    LabeledError::new("Invalid network idle threshold")
+                        .with_label("expected a non-negative number of milliseconds", ms.span)
+                })?,
+            None => DEFAULT_IDLE_THRESHOLD,
+        };
+        let wait_frames = match call.get_flag::<Spanned<i64>>("wait-for-frames")? {
+            Some(frames) => u32::try_from(frames.item).map_err(|_| {
+                LabeledError::new("Invalid frame count")
+                    .with_label("expected a non-negative number of frames", frames.span)
+            })?,
+            None => 0,
+        };
+        let idle_cpu = match call.get_flag::<Spanned<f64>>("wait-for-idle-cpu")? {
+            Some(percent) if !(0.0..=100.0).contains(&percent.item) => {
+                return Err(LabeledError::new("Invalid CPU threshold")
+                    .with_label("expected a percentage between 0 and 100", percent.span));
+            }
+            percent => percent.map(|percent| percent.item),
+        };
+        let port = match call.get_flag::<Spanned<i64>>("port")? {
+            Some(port) => {
+                let value = u16::try_from(port.item)
+                    .ok()
+                    .filter(|&p| p != 0)
+                    .ok_or_else(|| {
+                        LabeledError::new("Invalid port")
+                            .with_label("expected a port between 1 and 65535", port.span)
+                    })?;
+                // Chrome only reports a taken port as a generic launch timeout, so check it here.
+                if TcpListener::bind(("127.0.0.1", value)).is_err() {
+                    return Err(LabeledError::new(format!("Port {value} is already in use"))
+                        .with_label("port unavailable", port.span));
+                }
+                value
+            }
+            None => 0,
+        };
+        let dialog_response = match call.get_flag::<Spanned<String>>("js-dialog-response")? {
+            Some(response) => DialogResponse::parse(&response.item).ok_or_else(|| {
+                LabeledError::new("Invalid dialog response")
+                    .with_label("expected accept, dismiss or text:<string>", response.span)
+            })?,
+            None => DialogResponse::Dismiss,
+        };
+        let link_extraction = if call.has_flag("extract-links")? {
+            let filter = call
+                .get_flag::<Spanned<String>>("link-filter")?
+                .map(|pattern| {
+                    Regex::new(&pattern.item).map_err(|e| {
+                        LabeledError::new(format!("Invalid link filter: {e}"))
+                            .with_label("invalid regex", pattern.span)
+                    })
+                })
+                .transpose()?;
+            Some(LinkExtraction {
+                filter,
+                dedup: !call.has_flag("no-dedup")?,
+            })
+        } else {
+            None
+        };
+
+        let options = BrowseOptions {
+            stealth: !disable_stealth,
+            title_only,
+            disable_headless,
+            old_headless,
+            wait,
+            wait_min,
+            disable_gpu,
+            no_sandbox,
+            single_process,
+            deny_permissions,
+            print_media,
+            reduced_data,
+            bypass_service_worker,
+            extra_js_contexts,
+            content_css,
+            browser_language,
+            window_size,
+            simulate_touch,
+            battery,
+            idle_threshold,
+            wait_frames,
+            idle_cpu,
+            port,
+            dialog_response,
+            capture_websocket,
+            websocket_message,
+            performance_entry_types,
+            capture_resource_timing,
+            capture_errors,
+            capture_timing_breakdown,
+            fail_on_js_error,
+            response_hook,
+            storage_capture,
+            disappear_selector,
+            attribute_wait,
+            selector,
+            clip_html,
+            dump_dom,
+            shadow_dom,
+            link_extraction,
+            extract_feeds,
+            extract_canonical: extract_canonical || structured,
+            extract_hreflang,
+            extract_resource_hints,
+            extract_microdata,
+            strip_base64,
+            record_status: response_filter.is_some(),
+            request_interception,
+            blocked_types: if no_images {
+                vec![ResourceType::Image]
+            } else {
+                Vec::new()
+            },
+            cookies: loaded_jar
+                .as_ref()
+                .map(|(_, jar)| jar.clone())
+                .unwrap_or_default(),
+            save_cookies,
+            verbose,
+            pdf: None,
+        };
+
+        if call.has_flag("dry-run")? {
+            return dry_run(call, &url, structured, response_filter.as_deref(), &options);
+        }
+
+        let mut result = browse_page(&url, &options).map_err(|e| {
+            LabeledError::new(format!("{e}")).with_label("browse failed", call.head)
+        })?;
+
+        if let Some(title) = result.title.take() {
+            return Ok(Value::string(title, call.head));
+        }
+
+        if let (Some((path, mut jar)), Some(cookies)) = (loaded_jar, result.cookies.take()) {
+            cookie_jar::merge(&mut jar, cookies.iter().map(JarCookie::from));
+            fs::write(&path, cookie_jar::serialize(&jar)).map_err(|e| {
+                LabeledError::new(format!("Could not save cookies: {e}")).with_label(
+                    "cookie jar not written",
+                    cookies_file.as_ref().map_or(call.head, |file| file.span),
+                )
+            })?;
+        }
+
+        if let Some(statuses) = &response_filter
+            && !result
+                .status
+                .is_some_and(|status| statuses.contains(&status))
+        {
+            // Plain `nothing` is dropped by `each`, so filtering a list of URLs just works.
+            if !structured {
+                return Ok(Value::nothing(call.head));
+            }
+            let mut skipped = Record::new();
+            skipped.push("url", Value::string(url, call.head));
+            skipped.push(
+                "status",
+                result.status.map_or(Value::nothing(call.head), |status| {
+                    Value::int(status, call.head)
+                }),
+            );
+            let mut record = Record::new();
+            record.push("skipped", Value::record(skipped, call.head));
+            return Ok(Value::record(record, call.head));
+        }
+
+        Ok(result.into_value(structured, call.head))
+    }
+}
 
-        match browse_page(&url, !disable_stealth, disable_headless, wait) {
-            Ok(html) => Ok(Value::string(html, call.head)),
-            Err(e) => Err(LabeledError::new(format!("{e}")).with_label("browse failed", call.head)),
+/// Flags that only refine another one, and the flag they need.
+const FLAG_PARENTS: &[(&str, &str)] = &[
+    ("link-filter", "extract-links"),
+    ("no-dedup", "extract-links"),
+    ("idb-database", "capture-storage"),
+    ("strip-base64-threshold", "strip-base64"),
+];
+
+/// Every flag that only takes effect after `--title-only` has already returned.
+const TITLE_ONLY_CONFLICTS: &[&str] = &[
+    "content-css",
+    "wait",
+    "wait-min",
+    "wait-for-frames",
+    "wait-for-idle-cpu",
+    "wait-for-selector-to-disappear",
+    "wait-for-attribute",
+    "wait-for-attribute-name",
+    "wait-for-attribute-value",
+    "dump-dom",
+    "clip-html",
+    "selector",
+    "shadow-dom",
+    "extract-links",
+    "extract-feeds",
+    "extract-canonical",
+    "extract-hreflang",
+    "extract-resource-hints",
+    "extract-microdata",
+    "network-idle-threshold",
+    "capture-websocket",
+    "capture-performance-entries",
+    "capture-resource-timing",
+    "capture-errors",
+    "fail-on-js-error",
+    "evaluate-after-each-request",
+    "capture-timing-breakdown",
+    "capture-storage",
+    "wait-for-websocket-message",
+    "strip-base64",
+    "response-filter",
+    "save-cookies",
+    "structured",
+    "verbose",
+];
+
+/// Rejects flag combinations where one of the flags would be silently ignored.
+fn check_flag_conflicts(call: &EvaluatedCall) -> Result<(), LabeledError> {
+    for (flag, parent) in FLAG_PARENTS {
+        if let Some(span) = given_flag_span(call, flag)
+            && given_flag_span(call, parent).is_none()
+        {
+            return Err(LabeledError::new(format!("--{flag} needs --{parent}"))
+                .with_label(format!("only applies with --{parent}"), span));
         }
     }
+    if given_flag_span(call, "with-head").is_some()
+        && let Some(span) = given_flag_span(call, "headless-version")
+    {
+        return Err(
+            LabeledError::new("--headless-version conflicts with --with-head")
+                .with_label("a headed browser has no headless mode", span),
+        );
+    }
+    if given_flag_span(call, "title-only").is_some()
+        && let Some((flag, span)) = TITLE_ONLY_CONFLICTS
+            .iter()
+            .find_map(|flag| given_flag_span(call, flag).map(|span| (flag, span)))
+    {
+        return Err(
+            LabeledError::new(format!("--title-only conflicts with --{flag}"))
+                .with_label("--title-only returns before this runs", span),
+        );
+    }
+    Ok(())
+}
+
+/// The span of `flag` when it was passed, counting `--switch=false` as not passed.
+fn given_flag_span(call: &EvaluatedCall, flag: &str) -> Option<Span> {
+    call.named
+        .iter()
+        .find(|(name, _)| name.item == flag)
+        .filter(|(_, value)| !matches!(value, Some(Value::Bool { val: false, .. })))
+        .map(|(name, _)| name.span)
 }
 
-fn browse_page(
+fn dry_run(
+    call: &EvaluatedCall,
     url: &str,
+    structured: bool,
+    response_filter: Option<&[i64]>,
+    options: &BrowseOptions,
+) -> Result<Value, LabeledError> {
+    let span = call.positional.first().map_or(call.head, Value::span);
+    // Chrome refuses to navigate to anything without a scheme.
+    let scheme = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").expect("valid regex");
+    if !scheme.is_match(url) {
+        return Err(LabeledError::new("Invalid URL")
+            .with_label("expected an absolute URL such as https://example.com", span));
+    }
+    browser_config(options).map_err(|e| {
+        LabeledError::new(format!("Chrome can't be launched: {e}"))
+            .with_label("no browser to run", call.head)
+    })?;
+
+    let mut record = Record::new();
+    record.push("valid", Value::bool(true, call.head));
+    record.push("resolved_url", Value::string(url, span));
+    record.push(
+        "effective_flags",
+        Value::record(
+            effective_flags(call, structured, response_filter, options),
+            call.head,
+        ),
+    );
+    Ok(Value::record(record, call.head))
+}
+
+/// Every flag as the browse will actually use it, defaults and implied settings included.
+fn effective_flags(
+    call: &EvaluatedCall,
+    structured: bool,
+    response_filter: Option<&[i64]>,
+    options: &BrowseOptions,
+) -> Record {
+    let span = call.head;
+    let bool = |value: bool| Value::bool(value, span);
+    let string =
+        |value: Option<&str>| value.map_or(Value::nothing(span), |s| Value::string(s, span));
+    let duration = |value: Option<Duration>| {
+        value.map_or(Value::nothing(span), |d| {
+            Value::duration(d.as_nanos() as i64, span)
+        })
+    };
+    let strings = |values: &[String]| {
+        Value::list(
+            values.iter().map(|s| Value::string(s, span)).collect(),
+            span,
+        )
+    };
+    // Only the paths are meaningful to show, the options hold what was read from them.
+    let given = |flag: &str| call.get_flag_value(flag).unwrap_or(Value::nothing(span));
+    let content_css = match given("content-css") {
+        Value::Nothing { .. } => Value::list(Vec::new(), span),
+        list @ Value::List { .. } => list,
+        path => Value::list(vec![path], span),
+    };
+    let headless_version = match (options.disable_headless, options.old_headless) {
+        (true, _) => None,
+        (false, true) => Some("old"),
+        (false, false) => Some("new"),
+    };
+    let window_size = options
+        .window_size
+        .map_or(Value::nothing(span), |(width, height)| {
+            let mut size = Record::new();
+            size.push("width", Value::int(width.into(), span));
+            size.push("height", Value::int(height.into(), span));
+            Value::record(size, span)
+        });
+    let battery = options
+        .battery
+        .as_ref()
+        .map_or(Value::nothing(span), |battery| {
+            let mut state = Record::new();
+            state.push("charging", bool(battery.charging));
+            state.push("level", Value::float(battery.level, span));
+            Value::record(state, span)
+        });
+    let attribute_wait = options.attribute_wait.as_ref();
+    let link_extraction = options.link_extraction.as_ref();
+    let dialog_response = match &options.dialog_response {
+        DialogResponse::Accept => "accept".to_string(),
+        DialogResponse::Dismiss => "dismiss".to_string(),
+        DialogResponse::Text(text) => format!("text:{text}"),
+    };
+
+    let mut flags = Record::new();
+    flags.push("no-stealth", bool(!options.stealth));
+    flags.push("title-only", bool(options.title_only));
+    flags.push("with-head", bool(options.disable_headless));
+    flags.push("headless-version", string(headless_version));
+    flags.push("disable-gpu", bool(options.disable_gpu));
+    // `--disable-gpu` turns the sandbox off as well.
+    flags.push(
+        "no-sandbox",
+        bool(options.no_sandbox || options.disable_gpu),
+    );
+    flags.push("single-process", bool(options.single_process));
+    flags.push("disable-notifications", bool(options.deny_permissions));
+    flags.push("emulate-print-media", bool(options.print_media));
+    flags.push("prefers-reduced-data", bool(options.reduced_data));
+    flags.push("service-worker-bypass", bool(options.bypass_service_worker));
+    flags.push("extra-js-contexts", strings(&options.extra_js_contexts));
+    flags.push("content-css", content_css);
+    flags.push(
+        "browser-language",
+        string(options.browser_language.as_deref()),
+    );
+    flags.push("window-size", window_size);
+    flags.push("simulate-touch", bool(options.simulate_touch));
+    flags.push("emulate-battery", battery);
+    flags.push("wait", duration(options.wait));
+    flags.push("wait-min", duration(options.wait_min));
+    flags.push(
+        "wait-for-frames",
+        Value::int(options.wait_frames.into(), span),
+    );
+    flags.push(
+        "wait-for-idle-cpu",
+        options
+            .idle_cpu
+            .map_or(Value::nothing(span), |percent| Value::float(percent, span)),
+    );
+    flags.push(
+        "wait-for-selector-to-disappear",
+        string(options.disappear_selector.as_deref()),
+    );
+    flags.push(
+        "wait-for-attribute",
+        string(attribute_wait.map(|wait| wait.selector.as_str())),
+    );
+    flags.push(
+        "wait-for-attribute-name",
+        string(attribute_wait.map(|wait| wait.name.as_str())),
+    );
+    flags.push(
+        "wait-for-attribute-value",
+        string(attribute_wait.map(|wait| wait.value.as_str())),
+    );
+    flags.push("dump-dom", bool(options.dump_dom));
+    flags.push("clip-html", string(options.clip_html.as_deref()));
+    flags.push("selector", string(options.selector.as_deref()));
+    flags.push("shadow-dom", bool(options.shadow_dom));
+    flags.push("extract-links", bool(link_extraction.is_some()));
+    flags.push(
+        "link-filter",
+        string(link_extraction.and_then(|links| links.filter.as_ref().map(Regex::as_str))),
+    );
+    flags.push(
+        "no-dedup",
+        bool(link_extraction.is_some_and(|links| !links.dedup)),
+    );
+    flags.push("extract-feeds", bool(options.extract_feeds));
+    // Structured output always carries the canonical URL.
+    flags.push("extract-canonical", bool(options.extract_canonical));
+    flags.push("extract-hreflang", bool(options.extract_hreflang));
+    flags.push(
+        "extract-resource-hints",
+        bool(options.extract_resource_hints),
+    );
+    flags.push("extract-microdata", bool(options.extract_microdata));
+    flags.push(
+        "network-idle-threshold",
+        Value::int(options.idle_threshold.as_millis() as i64, span),
+    );
+    flags.push("js-dialog-response", Value::string(dialog_response, span));
+    // `0` lets chrome pick any free port.
+    flags.push(
+        "port",
+        match options.port {
+            0 => Value::nothing(span),
+            port => Value::int(port.into(), span),
+        },
+    );
+    flags.push("capture-websocket", bool(options.capture_websocket));
+    flags.push(
+        "capture-performance-entries",
+        strings(&options.performance_entry_types),
+    );
+    flags.push(
+        "capture-resource-timing",
+        bool(options.capture_resource_timing),
+    );
+    flags.push("capture-errors", bool(options.capture_errors));
+    flags.push("fail-on-js-error", bool(options.fail_on_js_error));
+    flags.push(
+        "evaluate-after-each-request",
+        string(options.response_hook.as_deref()),
+    );
+    flags.push(
+        "capture-timing-breakdown",
+        bool(options.capture_timing_breakdown),
+    );
+    flags.push("capture-storage", bool(options.storage_capture.is_some()));
+    flags.push(
+        "idb-database",
+        string(
+            options
+                .storage_capture
+                .as_ref()
+                .and_then(|storage| storage.idb_database.as_deref()),
+        ),
+    );
+    flags.push(
+        "wait-for-websocket-message",
+        string(
+            options
+                .websocket_message
+                .as_ref()
+                .map(MessagePattern::as_str),
+        ),
+    );
+    flags.push("strip-base64", bool(options.strip_base64.is_some()));
+    flags.push(
+        "strip-base64-threshold",
+        options
+            .strip_base64
+            .map_or(Value::nothing(span), |threshold| {
+                Value::int(threshold as i64, span)
+            }),
+    );
+    flags.push("no-images", bool(!options.blocked_types.is_empty()));
+    flags.push(
+        "response-filter",
+        response_filter.map_or(Value::nothing(span), |statuses| {
+            Value::list(
+                statuses
+                    .iter()
+                    .map(|&status| Value::int(status, span))
+                    .collect(),
+                span,
+            )
+        }),
+    );
+    flags.push("request-interception", given("request-interception"));
+    flags.push("cookies-file", given("cookies-file"));
+    flags.push("save-cookies", bool(options.save_cookies));
+    // Several capture flags switch on structured output by themselves.
+    flags.push("structured", bool(structured));
+    flags.push("verbose", bool(options.verbose));
+    flags
+}
+
+fn parse_window_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    let width = width.trim().parse().ok().filter(|&w: &u32| w > 0)?;
+    let height = height.trim().parse().ok().filter(|&h: &u32| h > 0)?;
+    Some((width, height))
+}
+
+fn parse_statuses(statuses: &str) -> Option<Vec<i64>> {
+    statuses
+        .split(',')
+        .map(|status| {
+            status
+                .trim()
+                .parse()
+                .ok()
+                .filter(|status| (100..=599).contains(status))
+        })
+        .collect()
+}
+
+/// A missing jar is only an error when there is nothing to save into it.
+fn load_cookie_jar(path: &Path, allow_missing: bool) -> Result<Vec<JarCookie>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(cookie_jar::parse(&contents)?),
+        Err(e) if allow_missing && e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// How long the network has to stay quiet for the page to count as loaded.
+const DEFAULT_IDLE_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// How long waiting for something on the page may take before the browse fails.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct BrowseOptions {
     stealth: bool,
+    /// Skip every wait and extraction, and only read `document.title`.
+    title_only: bool,
     disable_headless: bool,
+    old_headless: bool,
     wait: Option<Duration>,
-) -> Result<String, Box<dyn Error>> {
-    tokio::runtime::Runtime::new()?.block_on(async {
-        let mut browser_config = BrowserConfig::builder().port(0);
-        if disable_headless {
-            browser_config = browser_config.with_head()
+    /// Measured from navigation start.
+    wait_min: Option<Duration>,
+    disable_gpu: bool,
+    no_sandbox: bool,
+    single_process: bool,
+    deny_permissions: bool,
+    print_media: bool,
+    reduced_data: bool,
+    bypass_service_worker: bool,
+    /// Scripts evaluated on every new document, in order.
+    extra_js_contexts: Vec<String>,
+    /// Stylesheet contents injected once the page has settled.
+    content_css: Vec<String>,
+    /// BCP 47 language tag such as `fr-FR`.
+    browser_language: Option<String>,
+    window_size: Option<(u32, u32)>,
+    simulate_touch: bool,
+    battery: Option<Battery>,
+    idle_threshold: Duration,
+    /// Animation frames to wait for after the idle wait, `0` to skip.
+    wait_frames: u32,
+    /// CPU usage percentage below which the page counts as done.
+    idle_cpu: Option<f64>,
+    port: u16,
+    dialog_response: DialogResponse,
+    capture_websocket: bool,
+    websocket_message: Option<MessagePattern>,
+    performance_entry_types: Vec<String>,
+    capture_resource_timing: bool,
+    capture_errors: bool,
+    capture_timing_breakdown: bool,
+    fail_on_js_error: bool,
+    /// Script evaluated after every response.
+    response_hook: Option<String>,
+    storage_capture: Option<StorageCapture>,
+    /// Wait until no visible element matches this selector.
+    disappear_selector: Option<String>,
+    attribute_wait: Option<AttributeWait>,
+    selector: Option<String>,
+    /// Reduce the body to the element matching this selector before taking the HTML.
+    clip_html: Option<String>,
+    dump_dom: bool,
+    /// Resolve every selector flag through shadow roots too.
+    shadow_dom: bool,
+    link_extraction: Option<LinkExtraction>,
+    extract_feeds: bool,
+    extract_canonical: bool,
+    extract_hreflang: bool,
+    extract_resource_hints: bool,
+    extract_microdata: bool,
+    /// Data URIs longer than this many bytes are stripped from the HTML.
+    strip_base64: Option<usize>,
+    /// Remember the document's HTTP status for `--response-filter`.
+    record_status: bool,
+    request_interception: Option<Arc<RequestInterception>>,
+    /// Requests of these types are failed before they reach the network.
+    blocked_types: Vec<ResourceType>,
+    cookies: Vec<JarCookie>,
+    save_cookies: bool,
+    verbose: bool,
+    /// Print the page to a PDF once it has loaded, for `http browse pdf`.
+    pdf: Option<PrintToPdfParams>,
+}
+
+impl BrowseOptions {
+    /// Whether every request has to be paused for the interception closure to answer it.
+    /// Blocking resource types alone only pauses requests of those types.
+    fn intercepts_every_request(&self) -> bool {
+        self.request_interception.is_some()
+    }
+}
+
+#[derive(Default)]
+enum DialogResponse {
+    Accept,
+    #[default]
+    Dismiss,
+    /// Type the text into a `prompt()` and accept it.
+    Text(String),
+}
+
+impl DialogResponse {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "accept" => Some(Self::Accept),
+            "dismiss" => Some(Self::Dismiss),
+            _ => value
+                .strip_prefix("text:")
+                .map(|text| Self::Text(text.to_string())),
+        }
+    }
+
+    fn params(&self) -> HandleJavaScriptDialogParams {
+        match self {
+            Self::Accept => HandleJavaScriptDialogParams::new(true),
+            Self::Dismiss => HandleJavaScriptDialogParams::new(false),
+            Self::Text(text) => HandleJavaScriptDialogParams {
+                accept: true,
+                prompt_text: Some(text.clone()),
+            },
+        }
+    }
+}
+
+/// Aborts a background task once the page it was listening on is done with.
+pub struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+struct AttributeWait {
+    selector: String,
+    name: String,
+    value: String,
+}
+
+struct Battery {
+    charging: bool,
+    /// Charge level between `0.0` and `1.0`.
+    level: f64,
+}
+
+impl Battery {
+    fn from_value(value: &Value) -> Option<Self> {
+        let record = value.as_record().ok()?;
+        let charging = record.get("charging")?.as_bool().ok()?;
+        let level = record.get("level")?.coerce_float().ok()?;
+        (0.0..=1.0)
+            .contains(&level)
+            .then_some(Battery { charging, level })
+    }
+}
+
+struct StorageCapture {
+    idb_database: Option<String>,
+}
+
+struct LinkExtraction {
+    filter: Option<Regex>,
+    dedup: bool,
+}
+
+impl LinkExtraction {
+    fn apply(&self, links: Vec<String>) -> Vec<String> {
+        let mut seen = HashSet::new();
+        links
+            .into_iter()
+            .filter(|link| self.filter.as_ref().is_none_or(|re| re.is_match(link)))
+            .filter(|link| !self.dedup || seen.insert(link.clone()))
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct PageResult {
+    html: String,
+    /// Set instead of everything else with `--title-only`.
+    title: Option<String>,
+    /// Label the idle-wait script resolved with, e.g. `xhr-network-idle`.
+    idle_trigger: String,
+    /// Results of the `--extract-*` flags, keyed by their structured output column.
+    extractions: Vec<(&'static str, serde_json::Value)>,
+    /// Results of the `--capture-*` flags, only part of the structured output.
+    captures: Vec<(&'static str, serde_json::Value)>,
+    websocket_frames: Option<Vec<Frame>>,
+    /// HTTP status of the document response, if `record_status` was set and one arrived.
+    status: Option<i64>,
+    pdf: Option<Vec<u8>>,
+    /// Every cookie in the browser after the load, for `--save-cookies`.
+    cookies: Option<Vec<Cookie>>,
+}
+
+impl PageResult {
+    fn into_value(self, structured: bool, span: Span) -> Value {
+        let mut extractions = self
+            .extractions
+            .into_iter()
+            .map(|(column, json)| (column, json_to_value(json, span)));
+
+        if !structured {
+            // A single extraction replaces the HTML, several are returned side by side.
+            return match extractions.len() {
+                0 => Value::string(self.html, span),
+                1 => extractions.next().map(|(_, value)| value).unwrap(),
+                _ => Value::record(
+                    extractions
+                        .map(|(column, value)| (column.to_string(), value))
+                        .collect(),
+                    span,
+                ),
+            };
+        }
+
+        let mut record = Record::new();
+        record.push("html", Value::string(self.html, span));
+        record.push("idle_trigger", Value::string(self.idle_trigger, span));
+        for (column, value) in extractions {
+            record.push(column, value);
+        }
+        for (column, json) in self.captures {
+            record.push(column, json_to_value(json, span));
+        }
+        if let Some(frames) = self.websocket_frames {
+            record.push(
+                "websocket_frames",
+                Value::list(
+                    frames
+                        .into_iter()
+                        .map(|frame| frame.into_value(span))
+                        .collect(),
+                    span,
+                ),
+            );
+        }
+        Value::record(record, span)
+    }
+}
+
+fn json_to_value(json: serde_json::Value, span: Span) -> Value {
+    match json {
+        serde_json::Value::Null => Value::nothing(span),
+        serde_json::Value::Bool(b) => Value::bool(b, span),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::int(i, span),
+            None => Value::float(n.as_f64().unwrap_or(f64::NAN), span),
+        },
+        serde_json::Value::String(s) => Value::string(s, span),
+        serde_json::Value::Array(items) => Value::list(
+            items
+                .into_iter()
+                .map(|item| json_to_value(item, span))
+                .collect(),
+            span,
+        ),
+        serde_json::Value::Object(fields) => Value::record(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key, json_to_value(value, span)))
+                .collect(),
+            span,
+        ),
+    }
+}
+
+/// Shared across calls so the plugin doesn't spin up a fresh runtime for every page.
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+fn runtime() -> io::Result<&'static Runtime> {
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = Runtime::new()?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+/// Also checks that a chrome executable can be found, without launching it.
+fn browser_config(options: &BrowseOptions) -> Result<BrowserConfig, String> {
+    let mut browser_config = BrowserConfig::builder().port(options.port);
+    if options.disable_headless {
+        browser_config = browser_config.with_head()
+    } else if options.old_headless {
+        // Merged with the `--headless` chromiumoxide adds into `--headless=old`.
+        browser_config = browser_config.arg(("headless", "old"))
+    } else {
+        browser_config = browser_config.new_headless_mode()
+    };
+    if options.deny_permissions {
+        // Auto-denies every permission prompt (notifications, geolocation, camera, ...)
+        // so a dialog can't steal focus and stall the idle wait.
+        browser_config = browser_config.arg("deny-permission-prompts")
+    };
+    if options.disable_gpu {
+        // chromiumoxide merges repeated switches, so adding these twice is harmless.
+        browser_config = browser_config
+            .args(["disable-gpu", "disable-software-rasterizer"])
+            .no_sandbox()
+    };
+    if options.no_sandbox {
+        browser_config = browser_config.no_sandbox()
+    };
+    if options.single_process {
+        browser_config = browser_config.arg("single-process")
+    };
+
+    if let Some(language) = &options.browser_language {
+        browser_config = browser_config.arg(("lang", language.as_str()))
+    };
+
+    let mut viewport = Viewport::default();
+    if let Some((width, height)) = options.window_size {
+        viewport.width = width;
+        viewport.height = height;
+        browser_config = browser_config.window_size(width, height);
+    }
+    if options.simulate_touch {
+        viewport.emulating_mobile = true;
+        viewport.has_touch = true;
+    }
+    browser_config = browser_config.viewport(viewport);
+    if options.intercepts_every_request() {
+        browser_config = browser_config.enable_request_intercept();
+    }
+    browser_config.build()
+}
+
+fn browse_page(url: &str, options: &BrowseOptions) -> Result<PageResult, Box<dyn Error>> {
+    if options.disable_gpu {
+        eprintln!("warning: --disable-gpu also disables the chrome sandbox (--no-sandbox)");
+    }
+    if options.single_process {
+        eprintln!("warning: --single-process runs the browser, renderer and GPU in one process.");
+        eprintln!("warning: it saves memory, but a crashing page takes the whole browser down");
+        eprintln!("warning: and chrome does not officially support this mode.");
+    }
+    runtime()?.block_on(async {
+        let (mut browser, mut handler) = match Browser::launch(browser_config(options)?).await {
+            Ok(launched) => launched,
+            // The sandbox needs namespaces most containers don't grant, and chrome doesn't say so.
+            Err(e) if !options.no_sandbox && !options.disable_gpu && in_container() => {
+                return Err(format!(
+                    "{e}\nhint: this looks like a container, where chrome usually needs --no-sandbox"
+                )
+                .into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut handler_task = tokio::spawn(async move {
+            while let Some(event) = handler.next().await {
+                event?;
+            }
+            Ok::<_, CdpError>(())
+        });
+
+        let result = tokio::select! {
+            result = load_page(&browser, url, options) => result,
+            // The handler only stops on its own when the browser connection breaks.
+            exit = &mut handler_task => {
+                return Err(match exit {
+                    Ok(Ok(())) => "browser exited before the page finished loading".into(),
+                    Ok(Err(e)) => format!("browser connection failed: {e}").into(),
+                    Err(e) => e.into(),
+                });
+            }
         };
 
-        let (mut browser, mut handler) = Browser::launch(browser_config.build()?).await?;
+        let closed = browser.close().await;
+        if closed.is_ok() {
+            // The handler stream ends by itself once the close command is acknowledged.
+            handler_task.await??;
+            browser.wait().await?;
+        } else {
+            handler_task.abort();
+        }
+
+        let page = result?;
+        closed?;
+        Ok(page)
+    })
+}
+
+/// `fr-FR` becomes `fr-FR,fr;q=0.9`, the way chrome itself would send it.
+fn accept_language(language: &str) -> String {
+    match language.split_once('-') {
+        Some((primary, _)) => format!("{language},{primary};q=0.9"),
+        None => language.to_string(),
+    }
+}
 
-        let _task = tokio::spawn(async move { while let Some(_event) = handler.next().await {} });
+/// Docker and Podman leave a marker file at the root. Under cgroup v1, Docker and Kubernetes also
+/// show up in the control groups of PID 1, but cgroup v2 only reports `0::/` there.
+fn in_container() -> bool {
+    Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || fs::read_to_string("/proc/1/cgroup")
+            .is_ok_and(|cgroup| cgroup.contains("docker") || cgroup.contains("kubepods"))
+}
 
-        let page = browser.new_page(url).await?;
+async fn load_page(
+    browser: &Browser,
+    url: &str,
+    options: &BrowseOptions,
+) -> Result<PageResult, Box<dyn Error>> {
+    // Open a blank page first so emulation overrides are in place before navigation.
+    let page = browser.new_page("about:blank").await?;
 
-        if stealth {
-            page.enable_stealth_mode().await?;
+    // An unanswered dialog blocks the JS engine, and with it the idle wait.
+    let mut dialogs = page
+        .event_listener::<EventJavascriptDialogOpening>()
+        .await?;
+    let _dialog_task = AbortOnDrop(tokio::spawn({
+        let page = page.clone();
+        let params = options.dialog_response.params();
+        async move {
+            while dialogs.next().await.is_some() {
+                let _ = page.execute(params.clone()).await;
+            }
         }
+    }));
+
+    // `setEmulatedMedia` replaces the previous override wholesale, so send everything at once.
+    let mut media = SetEmulatedMediaParams::default();
+    if options.print_media {
+        media.media = Some(MediaTypeParams::Print.into());
+    }
+    if options.reduced_data {
+        media.features = Some(vec![MediaFeature::new("prefers-reduced-data", "reduce")]);
+    }
+    if media != SetEmulatedMediaParams::default() {
+        page.execute(media).await?;
+    }
+
+    // Likewise for `setExtraHTTPHeaders`.
+    let mut extra_headers = serde_json::Map::new();
+    if options.reduced_data {
+        extra_headers.insert("Save-Data".into(), "on".into());
+    }
+    if let Some(language) = &options.browser_language {
+        extra_headers.insert("Accept-Language".into(), accept_language(language).into());
+    }
+    if !extra_headers.is_empty() {
+        page.execute(SetExtraHttpHeadersParams::new(Headers::new(extra_headers)))
+            .await?;
+    }
+
+    if options.simulate_touch {
+        page.execute(
+            SetTouchEmulationEnabledParams::builder()
+                .enabled(true)
+                .max_touch_points(5)
+                .build()?,
+        )
+        .await?;
+    }
 
-        if let Some(duration) = wait {
-            tokio::time::sleep(duration).await;
+    for script in &options.extra_js_contexts {
+        page.evaluate_on_new_document(script.as_str()).await?;
+    }
+
+    if let Some(battery) = &options.battery {
+        // The DevTools protocol has no battery override, so `getBattery` is replaced instead.
+        page.evaluate_on_new_document(format!(
+            r#"(() => {{
+  const battery = Object.assign(new EventTarget(), {{
+    charging: {charging},
+    chargingTime: {charging} ? 0 : Infinity,
+    dischargingTime: Infinity,
+    level: {level},
+    onchargingchange: null,
+    onchargingtimechange: null,
+    ondischargingtimechange: null,
+    onlevelchange: null,
+  }});
+  Object.defineProperty(Navigator.prototype, 'getBattery', {{
+    value: () => Promise.resolve(battery),
+    configurable: true,
+  }});
+}})();"#,
+            charging = battery.charging,
+            level = battery.level
+        ))
+        .await?;
+    }
+
+    let mut websocket_capture = if options.capture_websocket || options.websocket_message.is_some()
+    {
+        Some(WebSocketCapture::start(&page, options.websocket_message.clone()).await?)
+    } else {
+        None
+    };
+
+    let interceptor = if options.intercepts_every_request() || !options.blocked_types.is_empty() {
+        Some(
+            Interceptor::start(
+                &page,
+                options.request_interception.clone(),
+                options.blocked_types.clone(),
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    let js_errors = if options.capture_errors || options.fail_on_js_error {
+        Some(JsErrorCapture::start(&page).await?)
+    } else {
+        None
+    };
+
+    let response_hook = match &options.response_hook {
+        Some(script) => Some(ResponseHook::start(&page, script.clone()).await?),
+        None => None,
+    };
+
+    let resource_timing = if options.capture_resource_timing {
+        Some(ResourceTimingCapture::start(&page).await?)
+    } else {
+        None
+    };
+
+    if !options.performance_entry_types.is_empty() {
+        // Started on every new document so entries from the initial load are observed too.
+        page.evaluate_on_new_document(format!(
+            r#"(() => {{
+  const entries = [];
+  const observer = new PerformanceObserver((list) => entries.push(...list.getEntries()));
+  for (const type of {types}) {{
+    try {{
+      observer.observe({{ type, buffered: true }});
+    }} catch (_) {{}}
+  }}
+  window.__nuBrowsePerformance = {{ entries, observer }};
+}})();"#,
+            types = serde_json::Value::from(options.performance_entry_types.clone())
+        ))
+        .await?;
+    }
+
+    if !options.cookies.is_empty() {
+        let params = options
+            .cookies
+            .iter()
+            .map(JarCookie::to_param)
+            .collect::<Result<_, _>>()?;
+        browser.set_cookies(params).await?;
+    }
+
+    if options.bypass_service_worker {
+        page.execute(service_worker::EnableParams::default())
+            .await?;
+        page.execute(service_worker::SetForceUpdateOnPageLoadParams::new(true))
+            .await?;
+        page.execute(SetBypassServiceWorkerParams::new(true))
+            .await?;
+        // Registrations and caches persist in the shared profile dir, so drop them on
+        // every new document as well.
+        page.evaluate_on_new_document(
+            r#"
+navigator.serviceWorker?.getRegistrations().then((registrations) =>
+  registrations.forEach((registration) => registration.unregister())
+);
+window.caches?.keys().then((keys) => keys.forEach((key) => caches.delete(key)));
+"#,
+        )
+        .await?;
+    }
+
+    if options.title_only {
+        let mut content_loaded = page.event_listener::<EventDomContentEventFired>().await?;
+        // `goto` only resolves on the load event, which can be long after the title is known.
+        tokio::select! {
+            navigated = page.goto(url) => {
+                navigated?;
+            }
+            _ = content_loaded.next() => {}
         }
+        let title: String = page.evaluate("document.title").await?.into_value()?;
+        return Ok(PageResult {
+            title: Some(title),
+            ..PageResult::default()
+        });
+    }
 
-        page.evaluate(
-            r#"() =>
+    let navigation_start = Instant::now();
+    page.goto(url).await?;
+
+    // The main frame keeps the request that loaded it, so iframe documents can't be mistaken for it.
+    let status = if options.record_status {
+        page.wait_for_navigation_response()
+            .await?
+            .and_then(|request| request.response.as_ref().map(|response| response.status))
+    } else {
+        None
+    };
+
+    if options.stealth {
+        page.enable_stealth_mode().await?;
+    }
+
+    if let Some(duration) = options.wait {
+        tokio::time::sleep(duration).await;
+    }
+
+    let idle_wait = r#"() =>
   new Promise((resolve) => {
     let activeRequests = 0;
     let idleTimer;
 
     const done = (label) => {
       clearTimeout(idleTimer);
-      idleTimer = setTimeout(() => resolve(`${label}-network-idle`), 500);
+      idleTimer = setTimeout(() => resolve(`${label}-network-idle`), IDLE_THRESHOLD_MS);
     };
 
     const origOpen = XMLHttpRequest.prototype.open;
@@ -146,17 +1817,545 @@ fn browse_page(
     };
 
     maybeResolveImmediately();
-  })"#,
+  })"#
+    .replace(
+        "IDLE_THRESHOLD_MS",
+        &options.idle_threshold.as_millis().to_string(),
+    );
+    let idle_trigger: String = page.evaluate(idle_wait).await?.into_value()?;
+
+    if options.verbose {
+        eprintln!("idle wait resolved: {idle_trigger}");
+    }
+
+    if let Some(minimum) = options.wait_min {
+        tokio::time::sleep(minimum.saturating_sub(navigation_start.elapsed())).await;
+    }
+
+    if options.wait_frames > 0 {
+        page.evaluate(format!(
+            r#"() =>
+  new Promise((resolve) => {{
+    let remaining = {frames};
+    const tick = () => (--remaining <= 0 ? resolve() : requestAnimationFrame(tick));
+    requestAnimationFrame(tick);
+  }})"#,
+            frames = options.wait_frames
+        ))
+        .await?;
+    }
+
+    if let Some(capture) = &mut websocket_capture {
+        capture.wait_for_match().await?;
+    }
+
+    if let Some(threshold) = options.idle_cpu {
+        wait_for_idle_cpu(&page, threshold, options.verbose).await?;
+    }
+
+    if let Some(selector) = &options.disappear_selector {
+        let hidden = format!(
+            "{elements}.every((element) => !element.checkVisibility({{ visibilityProperty: true, opacityProperty: true }}))",
+            elements = selector::query_all_js(selector, options.shadow_dom)
+        );
+        let what = format!("no visible element to match {selector:?}");
+        wait_until(&page, &hidden, options.shadow_dom, &what).await?;
+    }
+
+    if let Some(wait) = &options.attribute_wait {
+        let ready = format!(
+            "{lookup}?.getAttribute({name}) === {value}",
+            lookup = selector::query_js(&wait.selector, options.shadow_dom),
+            name = serde_json::Value::from(wait.name.as_str()),
+            value = serde_json::Value::from(wait.value.as_str())
+        );
+        let what = format!(
+            "the element matching {:?} to have {}={:?}",
+            wait.selector, wait.name, wait.value
+        );
+        wait_until(&page, &ready, options.shadow_dom, &what).await?;
+    }
+
+    for css in &options.content_css {
+        page.evaluate(format!(
+            r#"() => {{
+  const style = document.createElement('style');
+  style.textContent = {css};
+  (document.head ?? document.documentElement).appendChild(style);
+}}"#,
+            css = serde_json::Value::from(css.as_str())
+        ))
+        .await?;
+    }
+
+    let mut extractions = Vec::new();
+
+    if let Some(selector) = &options.selector {
+        let element = evaluate_json(
+            &page,
+            format!(
+                "() => {}?.outerHTML ?? null",
+                selector::query_js(selector, options.shadow_dom)
+            ),
+        )
+        .await?;
+        extractions.push(("element", element));
+    }
+
+    if let Some(extraction) = &options.link_extraction {
+        // `a.href` is already resolved against the document base URL.
+        let links: Vec<String> = page
+            .evaluate(
+                r#"() =>
+  Array.from(document.querySelectorAll('a[href]'))
+    .map((a) => a.href)
+    .filter((href) => typeof href === 'string' && href !== '')"#,
+            )
+            .await?
+            .into_value()?;
+        extractions.push(("links", extraction.apply(links).into()));
+    }
+
+    if options.extract_feeds {
+        let feeds = evaluate_json(
+            &page,
+            r#"() => {
+  const feedTypes = ['application/rss+xml', 'application/atom+xml', 'application/feed+json'];
+  return Array.from(document.querySelectorAll('link[rel~="alternate" i][href]'))
+    .filter((link) => feedTypes.includes(link.type.toLowerCase()))
+    .map((link) => ({ type: link.type, href: link.href }));
+}"#,
+        )
+        .await?;
+        extractions.push(("feeds", feeds));
+    }
+
+    if options.extract_canonical {
+        let canonical = evaluate_json(
+            &page,
+            r#"() => document.querySelector('link[rel~="canonical" i][href]')?.href ?? null"#,
+        )
+        .await?;
+        extractions.push(("canonical", canonical));
+    }
+
+    if options.extract_hreflang {
+        let alternates = evaluate_json(
+            &page,
+            r#"() =>
+  Array.from(document.querySelectorAll('link[rel~="alternate" i][hreflang][href]')).map((link) => ({
+    lang: link.hreflang,
+    href: link.href,
+  }))"#,
+        )
+        .await?;
+        extractions.push(("hreflang", alternates));
+    }
+
+    if options.extract_resource_hints {
+        let hints = evaluate_json(
+            &page,
+            r#"() => {
+  const hintRels = ['preload', 'prefetch', 'preconnect', 'modulepreload'];
+  return Array.from(document.querySelectorAll('link[rel][href]')).flatMap((link) =>
+    Array.from(link.relList)
+      .map((rel) => rel.toLowerCase())
+      .filter((rel) => hintRels.includes(rel))
+      .map((rel) => ({
+        rel,
+        href: link.href,
+        as: link.getAttribute('as'),
+        crossorigin: link.getAttribute('crossorigin'),
+        media: link.getAttribute('media'),
+      }))
+  );
+}"#,
+        )
+        .await?;
+        extractions.push(("resource_hints", hints));
+    }
+
+    if options.extract_microdata {
+        // Follows the "microdata to JSON" algorithm of the HTML spec.
+        let items = evaluate_json(
+            &page,
+            r#"() => {
+  const propertyValue = (element, memory) => {
+    if (element.hasAttribute('itemscope')) {
+      return memory.includes(element) ? 'ERROR' : item(element, memory);
+    }
+    const attribute = {
+      META: 'content',
+      AUDIO: 'src', EMBED: 'src', IFRAME: 'src', IMG: 'src', SOURCE: 'src', TRACK: 'src', VIDEO: 'src',
+      A: 'href', AREA: 'href', LINK: 'href',
+      OBJECT: 'data',
+      DATA: 'value', METER: 'value',
+      TIME: 'datetime',
+    }[element.tagName];
+    if (attribute === 'datetime' && !element.hasAttribute('datetime')) return element.textContent;
+    if (!attribute) return element.textContent;
+    // Resolve URL attributes against the document base.
+    return attribute === 'content' || attribute === 'value' || attribute === 'datetime'
+      ? element.getAttribute(attribute) ?? ''
+      : element[attribute] ?? '';
+  };
+
+  // The elements whose `itemprop` belongs to `root`, including those pulled in with `itemref`.
+  const propertyElements = (root) => {
+    const found = [];
+    const pending = [...root.children];
+    for (const id of (root.getAttribute('itemref') ?? '').split(/\s+/).filter(Boolean)) {
+      const referenced = document.getElementById(id);
+      if (referenced) pending.push(referenced);
+    }
+    const visited = new Set();
+    while (pending.length) {
+      const element = pending.shift();
+      if (element === root || visited.has(element)) continue;
+      visited.add(element);
+      if (element.hasAttribute('itemprop')) found.push(element);
+      if (!element.hasAttribute('itemscope')) pending.push(...element.children);
+    }
+    return found.sort((a, b) =>
+      a.compareDocumentPosition(b) & Node.DOCUMENT_POSITION_FOLLOWING ? -1 : 1
+    );
+  };
+
+  const item = (element, memory = []) => {
+    const result = {};
+    const types = (element.getAttribute('itemtype') ?? '').split(/\s+/).filter(Boolean);
+    if (types.length) result.type = types;
+    if (element.hasAttribute('itemid')) result.id = element.getAttribute('itemid');
+    const properties = {};
+    for (const property of propertyElements(element)) {
+      const value = propertyValue(property, [...memory, element]);
+      for (const name of property.getAttribute('itemprop').split(/\s+/).filter(Boolean)) {
+        (properties[name] ??= []).push(value);
+      }
+    }
+    result.properties = properties;
+    return result;
+  };
+
+  return Array.from(document.querySelectorAll('[itemscope]:not([itemprop])')).map((root) =>
+    item(root)
+  );
+}"#,
+        )
+        .await?;
+        extractions.push(("microdata", items));
+    }
+
+    let mut captures = Vec::new();
+
+    if !options.performance_entry_types.is_empty() {
+        let entries = evaluate_json(
+            &page,
+            r#"() => {
+  const capture = window.__nuBrowsePerformance;
+  if (!capture) return [];
+  capture.entries.push(...capture.observer.takeRecords());
+  return capture.entries.map((entry) => entry.toJSON());
+}"#,
         )
         .await?;
+        captures.push(("performance_entries", entries));
+    }
+
+    if let Some(capture) = js_errors {
+        let errors = capture.finish();
+        if options.fail_on_js_error
+            && let Some(first) = errors.first()
+        {
+            return Err(format!(
+                "the page threw {} uncaught exception(s), the first one: {}",
+                errors.len(),
+                first.message
+            )
+            .into());
+        }
+        if options.capture_errors {
+            let errors = errors.into_iter().map(|error| error.into_json()).collect();
+            captures.push(("errors", serde_json::Value::Array(errors)));
+        }
+    }
 
-        let html = page.content().await?;
-        browser.close().await?;
+    if let Some(hook) = response_hook {
+        captures.push(("hook_results", hook.finish()));
+    }
 
-        Ok(html)
+    if let Some(timing) = resource_timing {
+        captures.push(("resource_timing", timing.finish()));
+    }
+
+    if options.capture_timing_breakdown {
+        let breakdown = evaluate_json(
+            &page,
+            r#"() => {
+  const nav = performance.getEntriesByType('navigation')[0];
+  if (!nav) return null;
+  return {
+    dns_ms: nav.domainLookupEnd - nav.domainLookupStart,
+    // The TLS handshake is part of connectStart..connectEnd, so stop where it begins.
+    tcp_ms: (nav.secureConnectionStart > 0 ? nav.secureConnectionStart : nav.connectEnd) - nav.connectStart,
+    tls_ms: nav.secureConnectionStart > 0 ? nav.connectEnd - nav.secureConnectionStart : 0,
+    ttfb_ms: nav.responseStart - nav.requestStart,
+    download_ms: nav.responseEnd - nav.responseStart,
+    dom_interactive_ms: nav.domInteractive - nav.startTime,
+    dom_complete_ms: nav.domComplete - nav.startTime,
+  };
+}"#,
+        )
+        .await?;
+        captures.push(("timing_breakdown", breakdown));
+    }
+
+    if let Some(storage) = &options.storage_capture {
+        let dump = evaluate_json(
+            &page,
+            format!(
+                r#"() =>
+  (async () => {{
+    const toPromise = (request) =>
+      new Promise((resolve, reject) => {{
+        request.onsuccess = () => resolve(request.result);
+        request.onerror = () => reject(request.error);
+      }});
+    const databaseName = {database};
+
+    const indexedDb = [];
+    const databases = (await indexedDB.databases?.()) ?? [];
+    const info =
+      databaseName === null ? databases[0] : databases.find((db) => db.name === databaseName);
+    if (info) {{
+      const db = await toPromise(indexedDB.open(info.name));
+      for (const storeName of db.objectStoreNames) {{
+        const store = db.transaction(storeName, 'readonly').objectStore(storeName);
+        const [keys, values] = await Promise.all([
+          toPromise(store.getAllKeys()),
+          toPromise(store.getAll()),
+        ]);
+        keys.forEach((key, i) => indexedDb.push({{ store: storeName, key, value: values[i] }}));
+      }}
+      db.close();
+    }}
+
+    return {{
+      local_storage: {{ ...localStorage }},
+      session_storage: {{ ...sessionStorage }},
+      indexed_db: indexedDb,
+    }};
+  }})()"#,
+                database = serde_json::Value::from(storage.idb_database.clone())
+            ),
+        )
+        .await?;
+        captures.push(("storage", dump));
+    }
+
+    if let Some(selector) = &options.clip_html {
+        let clipped: bool = page
+            .evaluate(format!(
+                r#"() => {{
+  const element = {lookup};
+  if (!element) return false;
+  document.body.innerHTML = element.outerHTML;
+  return true;
+}}"#,
+                lookup = selector::query_js(selector, options.shadow_dom)
+            ))
+            .await?
+            .into_value()?;
+        if !clipped {
+            return Err(format!("no element matches the --clip-html selector {selector:?}").into());
+        }
+    }
+
+    let mut html = if options.dump_dom {
+        // The two serializations can differ for malformed markup.
+        let document = page.execute(GetDocumentParams::default()).await?;
+        let params = GetOuterHtmlParams {
+            node_id: Some(document.result.root.node_id),
+            ..GetOuterHtmlParams::default()
+        };
+        page.execute(params).await?.result.outer_html
+    } else {
+        page.content().await?
+    };
+    if let Some(threshold) = options.strip_base64 {
+        html = strip_data_uris(&html, threshold);
+    }
+    let pdf = match &options.pdf {
+        Some(params) => Some(page.pdf(params.clone()).await?),
+        None => None,
+    };
+    let cookies = if options.save_cookies {
+        Some(browser.get_cookies().await?)
+    } else {
+        None
+    };
+    // Kept answering until here, so requests made while taking the HTML or PDF aren't left paused.
+    if let Some(interceptor) = interceptor {
+        interceptor.finish()?;
+    }
+
+    Ok(PageResult {
+        html,
+        title: None,
+        idle_trigger,
+        extractions,
+        captures,
+        websocket_frames: websocket_capture
+            .filter(|_| options.capture_websocket)
+            .map(WebSocketCapture::finish),
+        status,
+        pdf,
+        cookies,
     })
 }
 
+fn strip_data_uris(html: &str, threshold: usize) -> String {
+    let data_uri =
+        Regex::new(r"data:[\w.+-]+/[\w.+-]+(?:;[\w.+-]+=[\w.+-]+)*;base64,[A-Za-z0-9+/]+=*")
+            .expect("valid regex");
+    data_uri
+        .replace_all(html, |captures: &regex::Captures| {
+            let uri = &captures[0];
+            if uri.len() > threshold {
+                "data:[STRIPPED]".to_string()
+            } else {
+                uri.to_string()
+            }
+        })
+        .into_owned()
+}
+
+/// Like `page.evaluate(..).into_value()`, except that `null` and `undefined` results come back as
+/// JSON `null` instead of an error.
+async fn evaluate_json(
+    page: &Page,
+    evaluation: impl Into<Evaluation>,
+) -> Result<serde_json::Value, CdpError> {
+    Ok(page
+        .evaluate(evaluation)
+        .await?
+        .value()
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Resolves once the JS expression `condition` is true. Mutations inside shadow roots never reach
+/// an observer on the document, so with `poll` the condition is checked on an interval instead.
+/// Fails after `WAIT_TIMEOUT`, naming `what` it was waiting for.
+async fn wait_until(
+    page: &Page,
+    condition: &str,
+    poll: bool,
+    what: &str,
+) -> Result<(), Box<dyn Error>> {
+    let evaluation = page.evaluate(format!(
+        r#"() =>
+  new Promise((resolve) => {{
+    const ready = () => {condition};
+    if (ready()) return resolve();
+    if ({poll}) {{
+      const interval = setInterval(() => {{
+        if (ready()) {{
+          clearInterval(interval);
+          resolve();
+        }}
+      }}, 100);
+      return;
+    }}
+    const observer = new MutationObserver(() => {{
+      if (ready()) {{
+        observer.disconnect();
+        resolve();
+      }}
+    }});
+    observer.observe(document, {{ subtree: true, childList: true, attributes: true }});
+  }})"#
+    ));
+    match tokio::time::timeout(WAIT_TIMEOUT, evaluation).await {
+        Ok(evaluated) => {
+            evaluated?;
+            Ok(())
+        }
+        Err(_) => Err(format!(
+            "gave up after {}s waiting for {what}",
+            WAIT_TIMEOUT.as_secs()
+        )
+        .into()),
+    }
+}
+
+/// Busy time is estimated from how much `TaskDuration` grows over a one second window.
+async fn wait_for_idle_cpu(page: &Page, threshold: f64, verbose: bool) -> Result<(), CdpError> {
+    const WINDOW: Duration = Duration::from_secs(1);
+    /// Pages that never settle, like ones running animations, shouldn't hang the command.
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    async fn task_duration(page: &Page) -> Result<f64, CdpError> {
+        Ok(page
+            .metrics()
+            .await?
+            .into_iter()
+            .find(|metric| metric.name == "TaskDuration")
+            .map_or(0.0, |metric| metric.value))
+    }
+
+    page.execute(performance::EnableParams::default()).await?;
+    let mut previous = task_duration(page).await?;
+    let start = Instant::now();
+    loop {
+        if start.elapsed() >= TIMEOUT {
+            if verbose {
+                eprintln!(
+                    "cpu usage stayed above {threshold}% for {}s, giving up",
+                    TIMEOUT.as_secs()
+                );
+            }
+            return Ok(());
+        }
+        tokio::time::sleep(WINDOW).await;
+        let current = task_duration(page).await?;
+        let usage = (current - previous) / WINDOW.as_secs_f64() * 100.0;
+        if verbose {
+            eprintln!("cpu usage: {usage:.1}%");
+        }
+        if usage < threshold {
+            return Ok(());
+        }
+        previous = current;
+    }
+}
+
 fn main() {
     serve_plugin(&HttpBrowse, MsgPackSerializer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_data_uris_under_the_threshold() {
+        let html = r#"<img src="data:image/png;base64,iVBORw0KGgo=">"#;
+        assert_eq!(strip_data_uris(html, 256), html);
+    }
+
+    #[test]
+    fn strips_data_uris_over_the_threshold() {
+        let html = format!(r#"<img src="data:image/png;base64,{}">"#, "A".repeat(300));
+        assert_eq!(
+            strip_data_uris(&html, 256),
+            r#"<img src="data:[STRIPPED]">"#
+        );
+    }
+
+    #[test]
+    fn strips_data_uris_with_parameters() {
+        let html = r#"<img src="data:image/svg+xml;charset=utf-8;base64,PHN2Zz48L3N2Zz4=">"#;
+        assert_eq!(strip_data_uris(html, 16), r#"<img src="data:[STRIPPED]">"#);
+    }
+}
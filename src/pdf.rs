@@ -0,0 +1,157 @@
+//! `http browse pdf`: printing a page, or HTML from the pipeline, to a PDF.
+
+use chromiumoxide::cdp::browser_protocol::page::PrintToPdfParams;
+use nu_plugin::{EngineInterface, EvaluatedCall, SimplePluginCommand};
+use nu_protocol::{Category, Example, LabeledError, Signature, SyntaxShape, Type, Value};
+use std::{
+    fs,
+    path::Path,
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{BrowseOptions, DEFAULT_IDLE_THRESHOLD, HttpBrowse, browse_page};
+
+pub struct HttpBrowsePdf;
+
+impl SimplePluginCommand for HttpBrowsePdf {
+    type Plugin = HttpBrowse;
+    fn name(&self) -> &str {
+        "http browse pdf"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("http browse pdf")
+            .input_output_types(vec![
+                (Type::Nothing, Type::Binary),
+                (Type::String, Type::Binary),
+            ])
+            .optional("url", SyntaxShape::String, "The URL to print")
+            .switch(
+                "from-html",
+                "Print the HTML string from the pipeline instead of fetching a URL",
+                None,
+            )
+            .switch("landscape", "Use landscape paper orientation", None)
+            .switch(
+                "print-background",
+                "Include background colors and images",
+                None,
+            )
+            .switch("no-stealth", "Disable stealth mode", None)
+            .switch(
+                "no-sandbox",
+                "Run chrome without its sandbox, needed in most Docker and CI containers",
+                None,
+            )
+            .named(
+                "wait",
+                SyntaxShape::Duration,
+                "Time to wait before printing",
+                Some('w'),
+            )
+            .category(Category::Network)
+    }
+
+    fn description(&self) -> &str {
+        "Render a page, or HTML from the pipeline, to a PDF using a headless browser."
+    }
+
+    fn extra_description(&self) -> &str {
+        "For this to work chrome/chromium has to be installed in the system."
+    }
+
+    fn examples(&'_ self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Save a page as a PDF",
+                example: "http browse pdf https://example.com | save example.pdf",
+                result: None,
+            },
+            Example {
+                description: "Turn a table into a PDF report",
+                example: "ls | to html | http browse pdf --from-html | save report.pdf",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &HttpBrowse,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let url: Option<String> = call.opt(0)?;
+        let from_html = call.has_flag("from-html")?;
+        let params = PrintToPdfParams {
+            landscape: Some(call.has_flag("landscape")?),
+            print_background: Some(call.has_flag("print-background")?),
+            ..PrintToPdfParams::default()
+        };
+        let options = BrowseOptions {
+            stealth: !call.has_flag("no-stealth")?,
+            no_sandbox: call.has_flag("no-sandbox")?,
+            wait: call.get_flag::<Duration>("wait")?,
+            idle_threshold: DEFAULT_IDLE_THRESHOLD,
+            pdf: Some(params),
+            ..BrowseOptions::default()
+        };
+
+        let result = match (url, from_html) {
+            (Some(_), true) => {
+                return Err(LabeledError::new("Both a URL and --from-html given")
+                    .with_label("pass either a URL or --from-html", call.head));
+            }
+            (Some(url), false) => browse_page(&url, &options),
+            (None, true) => {
+                let html = input.as_str().map_err(|_| {
+                    LabeledError::new("--from-html needs HTML from the pipeline")
+                        .with_label("expected a string input", input.span())
+                })?;
+                // Chrome refuses to navigate to large `data:` URLs, so go through a file.
+                let nanos = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_nanos());
+                let path = std::env::temp_dir()
+                    .join(format!("nu_plugin_browse-{}-{nanos}.html", process::id()));
+                fs::write(&path, html).map_err(|e| {
+                    LabeledError::new(format!("Could not write {}: {e}", path.display()))
+                        .with_label("temporary file not written", call.head)
+                })?;
+                let result = browse_page(&file_url(&path), &options);
+                let _ = fs::remove_file(&path);
+                result
+            }
+            (None, false) => {
+                return Err(LabeledError::new("Missing URL")
+                    .with_label("pass a URL or use --from-html", call.head));
+            }
+        };
+
+        let result = result.map_err(|e| {
+            LabeledError::new(format!("{e}")).with_label("browse failed", call.head)
+        })?;
+        Ok(Value::binary(result.pdf.unwrap_or_default(), call.head))
+    }
+}
+
+/// A `file://` URL for an absolute path, such as `file:///C:/Temp/page.html` on Windows.
+fn file_url(path: &Path) -> String {
+    let mut url = String::from("file://");
+    // Drive letter paths need an empty host in front of them.
+    if !path.starts_with("/") {
+        url.push('/');
+    }
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'\\' if cfg!(windows) => url.push('/'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b':' | b'-' | b'_' | b'.' | b'~' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    url
+}
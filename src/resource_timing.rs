@@ -0,0 +1,144 @@
+//! A lightweight network waterfall built from the DevTools `Network.*` loading events.
+
+use chromiumoxide::{
+    Page,
+    cdp::browser_protocol::network::{
+        EventLoadingFinished, EventRequestServedFromCache, EventRequestWillBeSent,
+        EventResponseReceived, RequestId,
+    },
+    error::CdpError,
+};
+use futures::{StreamExt, stream};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::AbortOnDrop;
+
+#[derive(Default)]
+struct Resource {
+    url: String,
+    mime_type: Option<String>,
+    /// Monotonic timestamps in seconds.
+    start: f64,
+    end: Option<f64>,
+    /// Bytes received before the body, i.e. the response headers.
+    header_size: f64,
+    transfer_size: f64,
+    cache_hit: bool,
+}
+
+enum LoadingEvent {
+    Sent(Arc<EventRequestWillBeSent>),
+    Cached(Arc<EventRequestServedFromCache>),
+    Response(Arc<EventResponseReceived>),
+    Finished(Arc<EventLoadingFinished>),
+}
+
+/// Records when every resource of the page started and finished loading.
+pub struct ResourceTimingCapture {
+    resources: Arc<Mutex<HashMap<RequestId, Resource>>>,
+    _task: AbortOnDrop,
+}
+
+impl ResourceTimingCapture {
+    /// Must be started before navigation so the document request itself is seen.
+    pub async fn start(page: &Page) -> Result<Self, CdpError> {
+        let sent = page.event_listener::<EventRequestWillBeSent>().await?;
+        let cached = page.event_listener::<EventRequestServedFromCache>().await?;
+        let response = page.event_listener::<EventResponseReceived>().await?;
+        let finished = page.event_listener::<EventLoadingFinished>().await?;
+        let mut events = stream::select(
+            stream::select(
+                sent.map(LoadingEvent::Sent),
+                cached.map(LoadingEvent::Cached),
+            ),
+            stream::select(
+                response.map(LoadingEvent::Response),
+                finished.map(LoadingEvent::Finished),
+            ),
+        );
+
+        let resources = Arc::new(Mutex::new(HashMap::new()));
+        let task = tokio::spawn({
+            let resources = resources.clone();
+            async move {
+                while let Some(event) = events.next().await {
+                    let mut resources = resources.lock().unwrap();
+                    match &event {
+                        LoadingEvent::Sent(ev) => {
+                            // A redirect reuses the request id, so only the last hop is kept.
+                            resources.insert(
+                                ev.request_id.clone(),
+                                Resource {
+                                    url: ev.request.url.clone(),
+                                    start: *ev.timestamp.inner(),
+                                    ..Resource::default()
+                                },
+                            );
+                        }
+                        LoadingEvent::Cached(ev) => {
+                            if let Some(resource) = resources.get_mut(&ev.request_id) {
+                                resource.cache_hit = true;
+                            }
+                        }
+                        LoadingEvent::Response(ev) => {
+                            if let Some(resource) = resources.get_mut(&ev.request_id) {
+                                let response = &ev.response;
+                                resource.url = response.url.clone();
+                                resource.mime_type = Some(response.mime_type.clone());
+                                resource.header_size = response.encoded_data_length;
+                                resource.cache_hit |= response.from_disk_cache.unwrap_or(false)
+                                    || response.from_prefetch_cache.unwrap_or(false);
+                            }
+                        }
+                        LoadingEvent::Finished(ev) => {
+                            if let Some(resource) = resources.get_mut(&ev.request_id) {
+                                resource.end = Some(*ev.timestamp.inner());
+                                resource.transfer_size = ev.encoded_data_length;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(ResourceTimingCapture {
+            resources,
+            _task: AbortOnDrop(task),
+        })
+    }
+
+    /// Every finished resource sorted by `start_time_ms`, with times relative to the first request.
+    pub fn finish(self) -> serde_json::Value {
+        let resources = std::mem::take(&mut *self.resources.lock().unwrap());
+        let origin = resources
+            .values()
+            .map(|resource| resource.start)
+            .fold(f64::INFINITY, f64::min);
+        let to_ms = |timestamp: f64| (timestamp - origin) * 1000.0;
+
+        let mut finished: Vec<Resource> = resources
+            .into_values()
+            .filter(|resource| resource.end.is_some())
+            .collect();
+        finished.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        finished
+            .into_iter()
+            .map(|resource| {
+                json!({
+                    "url": resource.url,
+                    "mime_type": resource.mime_type,
+                    "start_time_ms": to_ms(resource.start),
+                    "end_time_ms": resource.end.map(to_ms),
+                    "transfer_size_bytes": resource.transfer_size as i64,
+                    "encoded_size_bytes": (resource.transfer_size - resource.header_size).max(0.0) as i64,
+                    "cache_hit": resource.cache_hit,
+                })
+            })
+            .collect()
+    }
+}
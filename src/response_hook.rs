@@ -0,0 +1,49 @@
+//! `--evaluate-after-each-request`: running a script every time a response arrives.
+
+use chromiumoxide::{Page, cdp::browser_protocol::network::EventResponseReceived, error::CdpError};
+use futures::StreamExt;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+use crate::{AbortOnDrop, evaluate_json};
+
+/// Collects what the script evaluated to after each response, in arrival order.
+pub struct ResponseHook {
+    results: Arc<Mutex<Vec<serde_json::Value>>>,
+    _task: AbortOnDrop,
+}
+
+impl ResponseHook {
+    /// Must be started before navigation so the document response is seen.
+    pub async fn start(page: &Page, script: String) -> Result<Self, CdpError> {
+        let mut responses = page.event_listener::<EventResponseReceived>().await?;
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let task = tokio::spawn({
+            let page = page.clone();
+            let results = results.clone();
+            async move {
+                while let Some(event) = responses.next().await {
+                    // Early responses can arrive before the new document has a context to run in.
+                    let (result, error) = match evaluate_json(&page, script.as_str()).await {
+                        Ok(value) => (value, None),
+                        Err(e) => (serde_json::Value::Null, Some(e.to_string())),
+                    };
+                    results.lock().unwrap().push(json!({
+                        "url": event.response.url,
+                        "result": result,
+                        "error": error,
+                    }));
+                }
+            }
+        });
+
+        Ok(ResponseHook {
+            results,
+            _task: AbortOnDrop(task),
+        })
+    }
+
+    pub fn finish(self) -> serde_json::Value {
+        std::mem::take(&mut *self.results.lock().unwrap()).into()
+    }
+}
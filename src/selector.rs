@@ -0,0 +1,53 @@
+//! CSS selector lookups shared by every flag that targets an element.
+
+/// Like `document.querySelector`, but also descends into every open `shadowRoot`.
+const DEEP_QUERY_SELECTOR: &str = r#"function deepQuerySelector(selector, root = document) {
+  const found = root.querySelector(selector);
+  if (found) return found;
+  for (const element of root.querySelectorAll('*')) {
+    if (element.shadowRoot) {
+      const nested = deepQuerySelector(selector, element.shadowRoot);
+      if (nested) return nested;
+    }
+  }
+  return null;
+}"#;
+
+/// Like `document.querySelectorAll`, but also collects matches inside every open `shadowRoot`.
+const DEEP_QUERY_SELECTOR_ALL: &str = r#"function deepQuerySelectorAll(selector, root = document) {
+  const found = [...root.querySelectorAll(selector)];
+  for (const element of root.querySelectorAll('*')) {
+    if (element.shadowRoot) found.push(...deepQuerySelectorAll(selector, element.shadowRoot));
+  }
+  return found;
+}"#;
+
+const QUERY_SELECTOR: &str = "(selector) => document.querySelector(selector)";
+
+const QUERY_SELECTOR_ALL: &str = "(selector) => [...document.querySelectorAll(selector)]";
+
+/// A JS expression evaluating to the first element matching `selector`, or `null`.
+pub fn query_js(selector: &str, shadow_dom: bool) -> String {
+    let lookup = if shadow_dom {
+        DEEP_QUERY_SELECTOR
+    } else {
+        QUERY_SELECTOR
+    };
+    call(lookup, selector)
+}
+
+/// A JS expression evaluating to an array of every element matching `selector`.
+pub fn query_all_js(selector: &str, shadow_dom: bool) -> String {
+    let lookup = if shadow_dom {
+        DEEP_QUERY_SELECTOR_ALL
+    } else {
+        QUERY_SELECTOR_ALL
+    };
+    call(lookup, selector)
+}
+
+fn call(lookup: &str, selector: &str) -> String {
+    // A JSON string is also a valid JS string literal.
+    let selector = serde_json::Value::from(selector);
+    format!("({lookup})({selector})")
+}
@@ -0,0 +1,196 @@
+//! WebSocket frame capture over the DevTools `Network.webSocket*` events.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chromiumoxide::{
+    Page,
+    cdp::browser_protocol::network::{
+        EventWebSocketCreated, EventWebSocketFrameReceived, EventWebSocketFrameSent, WebSocketFrame,
+    },
+    error::CdpError,
+};
+use futures::{StreamExt, stream};
+use nu_protocol::{Record, Span, Value};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+use crate::{AbortOnDrop, WAIT_TIMEOUT};
+
+enum Direction {
+    Sent,
+    Received,
+}
+
+enum Payload {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Payload {
+    fn from_frame(frame: &WebSocketFrame) -> Self {
+        // Opcode 1 is a text frame, everything else arrives base64 encoded.
+        if frame.opcode == 1.0 {
+            return Payload::Text(frame.payload_data.clone());
+        }
+        match STANDARD.decode(&frame.payload_data) {
+            Ok(bytes) => Payload::Binary(bytes),
+            Err(_) => Payload::Text(frame.payload_data.clone()),
+        }
+    }
+}
+
+/// Pattern an incoming frame has to match for `--wait-for-websocket-message`.
+#[derive(Clone)]
+pub struct MessagePattern {
+    text: String,
+    regex: Option<Regex>,
+}
+
+impl MessagePattern {
+    /// The pattern matches as a plain substring, or as a regex when it is a valid one.
+    pub fn new(text: String) -> Self {
+        let regex = Regex::new(&text).ok();
+        MessagePattern { text, regex }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    fn matches(&self, payload: &Payload) -> bool {
+        let text = match payload {
+            Payload::Text(text) => text.as_str(),
+            Payload::Binary(bytes) => &String::from_utf8_lossy(bytes),
+        };
+        text.contains(&self.text) || self.regex.as_ref().is_some_and(|re| re.is_match(text))
+    }
+}
+
+pub struct Frame {
+    direction: Direction,
+    url: Option<String>,
+    timestamp: f64,
+    payload: Payload,
+}
+
+impl Frame {
+    pub fn into_value(self, span: Span) -> Value {
+        let direction = match self.direction {
+            Direction::Sent => "sent",
+            Direction::Received => "received",
+        };
+        let payload = match self.payload {
+            Payload::Text(text) => Value::string(text, span),
+            Payload::Binary(bytes) => Value::binary(bytes, span),
+        };
+
+        let mut record = Record::new();
+        record.push("direction", Value::string(direction, span));
+        record.push(
+            "url",
+            self.url
+                .map_or(Value::nothing(span), |url| Value::string(url, span)),
+        );
+        record.push("timestamp", Value::float(self.timestamp, span));
+        record.push("payload", payload);
+        Value::record(record, span)
+    }
+}
+
+enum SocketEvent {
+    Created(Arc<EventWebSocketCreated>),
+    Received(Arc<EventWebSocketFrameReceived>),
+    Sent(Arc<EventWebSocketFrameSent>),
+}
+
+/// Records every WebSocket frame the page sends or receives.
+pub struct WebSocketCapture {
+    frames: Arc<Mutex<Vec<Frame>>>,
+    /// The pattern to wait for, and what fires once a frame matched it.
+    matched: Option<(String, oneshot::Receiver<()>)>,
+    _task: AbortOnDrop,
+}
+
+impl WebSocketCapture {
+    /// Must be started before navigation so sockets opened during load are seen.
+    pub async fn start(page: &Page, wait_for: Option<MessagePattern>) -> Result<Self, CdpError> {
+        let created = page.event_listener::<EventWebSocketCreated>().await?;
+        let received = page.event_listener::<EventWebSocketFrameReceived>().await?;
+        let sent = page.event_listener::<EventWebSocketFrameSent>().await?;
+        let mut events = stream::select(
+            created.map(SocketEvent::Created),
+            stream::select(
+                received.map(SocketEvent::Received),
+                sent.map(SocketEvent::Sent),
+            ),
+        );
+
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let (mut matched_tx, matched) = wait_for.is_some().then(oneshot::channel).unzip();
+        let matched = wait_for.as_ref().map(|p| p.text.clone()).zip(matched);
+        let task = tokio::spawn({
+            let frames = frames.clone();
+            async move {
+                let mut urls = HashMap::new();
+                while let Some(event) = events.next().await {
+                    let (direction, request_id, timestamp, frame) = match &event {
+                        SocketEvent::Created(created) => {
+                            urls.insert(created.request_id.clone(), created.url.clone());
+                            continue;
+                        }
+                        SocketEvent::Received(ev) => (
+                            Direction::Received,
+                            &ev.request_id,
+                            &ev.timestamp,
+                            &ev.response,
+                        ),
+                        SocketEvent::Sent(ev) => {
+                            (Direction::Sent, &ev.request_id, &ev.timestamp, &ev.response)
+                        }
+                    };
+                    let frame = Frame {
+                        direction,
+                        url: urls.get(request_id).cloned(),
+                        timestamp: *timestamp.inner(),
+                        payload: Payload::from_frame(frame),
+                    };
+                    if matches!(frame.direction, Direction::Received)
+                        && wait_for.as_ref().is_some_and(|p| p.matches(&frame.payload))
+                        && let Some(tx) = matched_tx.take()
+                    {
+                        let _ = tx.send(());
+                    }
+                    frames.lock().unwrap().push(frame);
+                }
+            }
+        });
+
+        Ok(WebSocketCapture {
+            frames,
+            matched,
+            _task: AbortOnDrop(task),
+        })
+    }
+
+    /// Resolves once an incoming frame matched the pattern given to [`WebSocketCapture::start`],
+    /// or fails when none did within `WAIT_TIMEOUT`.
+    pub async fn wait_for_match(&mut self) -> Result<(), String> {
+        if let Some((pattern, matched)) = self.matched.take()
+            && tokio::time::timeout(WAIT_TIMEOUT, matched).await.is_err()
+        {
+            return Err(format!(
+                "no WebSocket frame matched {pattern:?} within {}s",
+                WAIT_TIMEOUT.as_secs()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Frames captured so far, in the order they were seen.
+    pub fn finish(self) -> Vec<Frame> {
+        std::mem::take(&mut *self.frames.lock().unwrap())
+    }
+}